@@ -0,0 +1,215 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::pacdiff::{format_time, private_temp_dir};
+use crate::util::escape_path;
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+use which::which;
+
+const SNIFF_LEN: usize = 8192;
+
+struct Converter {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    magic: &'static [u8],
+    command: &'static [&'static str],
+}
+
+const CONVERTERS: &[Converter] = &[Converter {
+    name: "certificate",
+    extensions: &["crt", "cer", "der"],
+    magic: &[0x30, 0x82],
+    command: &[
+        "openssl", "x509", "-inform", "der", "-noout", "-text", "-in",
+    ],
+}];
+
+fn head(path: &Path) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(SNIFF_LEN as u64).read_to_end(&mut buf);
+    }
+    buf
+}
+
+pub fn is_binary(path: &Path) -> bool {
+    let buf = head(path);
+
+    if buf.contains(&0) {
+        return true;
+    }
+
+    match std::str::from_utf8(&buf) {
+        Ok(_) => false,
+        // a multibyte character cut off by the sniff length is not an error
+        Err(err) => err.error_len().is_some(),
+    }
+}
+
+fn sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn describe(path: &Path) -> Vec<String> {
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(err) => return vec![err.to_string()],
+    };
+
+    vec![
+        metadata.len().to_string(),
+        sha256(path).unwrap_or_else(|e| e.to_string()),
+        format!("{:04o}", metadata.mode() & 0o7777),
+        format!("{}:{}", metadata.uid(), metadata.gid()),
+        format_time(metadata.modified().ok()),
+    ]
+}
+
+fn print_comparison(config: &Config, files: &[(&str, &Path)]) {
+    let b = config.color.bold;
+    let fields = ["Size", "SHA-256", "Mode", "Owner", "Modified"];
+    let columns = files
+        .iter()
+        .map(|(_, path)| describe(path))
+        .collect::<Vec<_>>();
+
+    let maxfield = fields.iter().map(|f| f.len()).max().unwrap_or(0);
+    let widths = files
+        .iter()
+        .zip(&columns)
+        .map(|((name, _), column)| {
+            column
+                .iter()
+                .map(|s| s.len())
+                .chain(Some(name.len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = format!("{:w$}", "", w = maxfield);
+    for ((name, _), width) in files.iter().zip(&widths) {
+        header.push_str(&format!("    {:w$}", name, w = width));
+    }
+    println!("{}", b.paint(header.trim_end()));
+
+    for (n, field) in fields.iter().enumerate() {
+        let mut row = format!("{:w$}", field, w = maxfield);
+        for (column, width) in columns.iter().zip(&widths) {
+            let value = column.get(n).map(|s| s.as_str()).unwrap_or("");
+            row.push_str(&format!("    {:w$}", value, w = width));
+        }
+        println!("{}", row.trim_end());
+    }
+}
+
+fn converter(path: &Path) -> Option<&'static Converter> {
+    let buf = head(path);
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    CONVERTERS.iter().find(|c| {
+        (c.extensions.contains(&extension) || buf.starts_with(c.magic))
+            && which(c.command[0]).is_ok()
+    })
+}
+
+fn convert(converter: &Converter, path: &Path) -> Result<Vec<u8>> {
+    let bin = converter.command[0];
+    let mut command = Command::new(bin);
+    command.args(&converter.command[1..]).arg(path);
+
+    let args = || {
+        converter.command[1..]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(Some(escape_path(path)))
+            .collect()
+    };
+
+    let output = command
+        .output()
+        .map_err(|err| Error::CommandFailed(bin.into(), args(), err))?;
+
+    if !output.status.success() {
+        return Err(Error::CommandNonZero(
+            bin.into(),
+            args(),
+            output.status.code(),
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+fn hexdump(path: &Path) -> Result<Vec<u8>> {
+    let data = fs::read(path).map_err(|e| Error::File("read", path.into(), e))?;
+    let mut out = Vec::new();
+
+    for (n, chunk) in data.chunks(16).enumerate() {
+        let hex = chunk
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        let _ = writeln!(out, "{:08x}  {:47}  |{}|", n * 16, hex, ascii);
+    }
+
+    Ok(out)
+}
+
+// returns the temporary directory along with the rendered files, the caller removes it
+pub fn render(
+    config: &Config,
+    names: (&str, &str),
+    live: &Path,
+    pacfile: &Path,
+) -> Result<(PathBuf, PathBuf, PathBuf)> {
+    print_comparison(config, &[(names.0, live), (names.1, pacfile)]);
+
+    let converter = converter(pacfile).or_else(|| converter(live));
+    let description = converter.map(|c| c.name).unwrap_or("hexdump");
+    println!(
+        "{} binary file, showing {} view",
+        config.color.info.paint("note:"),
+        description
+    );
+
+    let dir = private_temp_dir()?;
+
+    let stem = escape_path(Path::new(pacfile.file_name().unwrap_or_default()));
+    let mut rendered = Vec::new();
+
+    for (name, path) in &[(names.0, live), (names.1, pacfile)] {
+        let data = match converter {
+            Some(converter) => convert(converter, path).or_else(|_| hexdump(path))?,
+            None => hexdump(path)?,
+        };
+
+        let out = dir.join(format!("{}.{}", stem, name));
+        fs::write(&out, data).map_err(|e| Error::File("write", out.clone(), e))?;
+        rendered.push(out);
+    }
+
+    let pacfile = rendered.pop().unwrap();
+    let live = rendered.pop().unwrap();
+    Ok((dir, live, pacfile))
+}
@@ -0,0 +1,111 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::pacdiff::{Backup, Kind};
+
+use std::time::{Duration, SystemTime};
+
+pub const OK: i32 = 0;
+pub const WARNING: i32 = 1;
+pub const CRITICAL: i32 = 2;
+pub const UNKNOWN: i32 = 3;
+
+const DAY: u64 = 60 * 60 * 24;
+
+pub fn check(config: &Config, backups: &[Backup], errors: &[Error]) -> i32 {
+    let now = SystemTime::now();
+    let mut pacnew = 0;
+    let mut pacsave = 0;
+    let mut oldest: Option<(Duration, Kind)> = None;
+
+    for backup in backups {
+        for file in &backup.pacfiles {
+            match backup.kind {
+                Kind::Pacnew => pacnew += 1,
+                Kind::Pacsave => pacsave += 1,
+            }
+
+            let age = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|m| now.duration_since(m).ok());
+
+            match (age, oldest) {
+                (Some(age), Some((old, _))) if age <= old => (),
+                (Some(age), _) => oldest = Some((age, backup.kind)),
+                (None, _) => (),
+            }
+        }
+    }
+
+    let count = pacnew + pacsave;
+    let mut counts = Vec::new();
+
+    if pacnew != 0 {
+        counts.push(format!("{} pacnew", pacnew));
+    }
+    if pacsave != 0 {
+        counts.push(format!("{} pacsave", pacsave));
+    }
+
+    let counts = if counts.is_empty() {
+        "no pacfiles".to_string()
+    } else {
+        counts.join(", ")
+    };
+
+    let age_exceeded = |days: Option<u64>| match (days, oldest) {
+        (Some(days), Some((age, kind))) if age.as_secs() >= days.saturating_mul(DAY) => {
+            Some(format!("{} older than {} days", kind, days))
+        }
+        _ => None,
+    };
+
+    let count_exceeded = |max: Option<usize>| match max {
+        Some(max) => count != 0 && count >= max,
+        None => false,
+    };
+
+    let (status, message) = if let Some(message) = age_exceeded(config.critical_age) {
+        (CRITICAL, message)
+    } else if count_exceeded(config.critical_count) {
+        (CRITICAL, counts)
+    } else if let Some(message) = age_exceeded(config.warning_age) {
+        (WARNING, message)
+    } else if count_exceeded(Some(config.warning_count)) {
+        (WARNING, counts)
+    } else {
+        (OK, counts)
+    };
+
+    // pacfiles may have been missed, so the counts can't be trusted
+    let (status, message) = match errors.first() {
+        Some(err) => (
+            UNKNOWN,
+            format!(
+                "{} errors while looking for pacfiles: {}",
+                errors.len(),
+                err
+            ),
+        ),
+        None => (status, message),
+    };
+
+    let name = match status {
+        OK => "OK",
+        WARNING => "WARNING",
+        CRITICAL => "CRITICAL",
+        _ => "UNKNOWN",
+    };
+
+    println!(
+        "{}: {} | pacnew={} pacsave={} oldest={}s",
+        name,
+        message,
+        pacnew,
+        pacsave,
+        oldest.map(|(age, _)| age.as_secs()).unwrap_or(0)
+    );
+
+    status
+}
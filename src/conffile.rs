@@ -0,0 +1,282 @@
+use crate::config::{parse_color, Colors, Config};
+use crate::error::{Error, Result};
+
+use std::env;
+use std::ffi::{CStr, CString, OsStr};
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use ansi_term::{Color, Style};
+use serde::Deserialize;
+
+const SYSTEM_CONFIG: &str = "/etc/pacdiffviewer.conf";
+const USER_CONFIG: &str = "pacdiffviewer/config.toml";
+
+const COLORS: &[&str] = &["never", "auto", "always"];
+const COLUMNS: &[&str] = &["root", "package", "file", "kind", "modified"];
+const KINDS: &[&str] = &["pacnew", "pacsave"];
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Theme {
+    bold: Option<String>,
+    error: Option<String>,
+    prompt: Option<String>,
+    info: Option<String>,
+    pacnew: Option<String>,
+    pacsave: Option<String>,
+    age: Option<String>,
+}
+
+impl Theme {
+    fn roles(&self) -> [(&'static str, &Option<String>); 7] {
+        [
+            ("bold", &self.bold),
+            ("error", &self.error),
+            ("prompt", &self.prompt),
+            ("info", &self.info),
+            ("pacnew", &self.pacnew),
+            ("pacsave", &self.pacsave),
+            ("age", &self.age),
+        ]
+    }
+
+    fn merge(&mut self, other: Theme) {
+        self.bold = other.bold.or_else(|| self.bold.take());
+        self.error = other.error.or_else(|| self.error.take());
+        self.prompt = other.prompt.or_else(|| self.prompt.take());
+        self.info = other.info.or_else(|| self.info.take());
+        self.pacnew = other.pacnew.or_else(|| self.pacnew.take());
+        self.pacsave = other.pacsave.or_else(|| self.pacsave.take());
+        self.age = other.age.or_else(|| self.age.take());
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Settings {
+    diffprog: Option<String>,
+    mergeprog: Option<String>,
+    sudoedit: Option<bool>,
+    color: Option<String>,
+    columns: Option<Vec<String>>,
+    kinds: Option<Vec<String>>,
+    colors: Theme,
+}
+
+impl Settings {
+    fn merge(&mut self, other: Settings) {
+        self.diffprog = other.diffprog.or_else(|| self.diffprog.take());
+        self.mergeprog = other.mergeprog.or_else(|| self.mergeprog.take());
+        self.sudoedit = other.sudoedit.or(self.sudoedit);
+        self.color = other.color.or_else(|| self.color.take());
+        self.columns = other.columns.or_else(|| self.columns.take());
+        self.kinds = other.kinds.or_else(|| self.kinds.take());
+        self.colors.merge(other.colors);
+    }
+}
+
+fn home_of(user: &str) -> Option<PathBuf> {
+    let user = CString::new(user).ok()?;
+
+    // getpwnam returns a pointer into static storage, copy it out straight away
+    unsafe {
+        let pw = libc::getpwnam(user.as_ptr());
+        if pw.is_null() || (*pw).pw_dir.is_null() {
+            return None;
+        }
+        let dir = CStr::from_ptr((*pw).pw_dir);
+        Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+    }
+}
+
+fn user_config(config: &Config) -> Option<PathBuf> {
+    // under sudo the environment belongs to root, so go by the invoking user's home
+    if let Some(ref user) = config.sudouser {
+        return home_of(user).map(|home| home.join(".config").join(USER_CONFIG));
+    }
+
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join(USER_CONFIG)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join(USER_CONFIG)),
+    }
+}
+
+fn parse_color_name(name: &str) -> Option<Color> {
+    let color = match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "purple" | "magenta" => Color::Purple,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        _ if name.starts_with('#') && name.len() == 7 => {
+            let rgb = u32::from_str_radix(&name[1..], 16).ok()?;
+            Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        }
+        _ => Color::Fixed(name.parse().ok()?),
+    };
+
+    Some(color)
+}
+
+// styles are written as e.g. "bold red", "underline 208 on black" or "#ff8800"
+fn parse_style(spec: &str) -> std::result::Result<Style, String> {
+    let mut style = Style::new();
+    let mut words = spec.split_whitespace();
+
+    while let Some(word) = words.next() {
+        style = match word {
+            "none" | "plain" => style,
+            "bold" => style.bold(),
+            "dimmed" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "blink" => style.blink(),
+            "reverse" => style.reverse(),
+            "on" => match words.next().and_then(parse_color_name) {
+                Some(color) => style.on(color),
+                None => return Err(format!("missing or invalid background color in '{}'", spec)),
+            },
+            _ => match parse_color_name(word) {
+                Some(color) => style.fg(color),
+                None => {
+                    return Err(format!(
+                        "unknown color or attribute '{}' in '{}'",
+                        word, spec
+                    ))
+                }
+            },
+        };
+    }
+
+    Ok(style)
+}
+
+fn check(path: &Path, key: &str, values: &[String], allowed: &[&str]) -> Result<()> {
+    match values.iter().find(|v| !allowed.contains(&v.as_str())) {
+        Some(value) => Err(Error::Conffile(
+            path.into(),
+            format!(
+                "unknown {} '{}', expected one of: {}",
+                key,
+                value,
+                allowed.join(", ")
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn read(path: &Path) -> Result<Settings> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(err) => return Err(Error::File("read", path.into(), err)),
+    };
+
+    let settings: Settings =
+        toml::from_str(&data).map_err(|e| Error::Conffile(path.into(), e.to_string()))?;
+
+    if let Some(ref color) = settings.color {
+        check(path, "color", std::slice::from_ref(color), COLORS)?;
+    }
+    if let Some(ref columns) = settings.columns {
+        check(path, "column", columns, COLUMNS)?;
+    }
+    if let Some(ref kinds) = settings.kinds {
+        check(path, "kind", kinds, KINDS)?;
+    }
+    for (_, spec) in settings.colors.roles().iter() {
+        if let Some(spec) = spec {
+            parse_style(spec).map_err(|e| Error::Conffile(path.into(), e))?;
+        }
+    }
+
+    Ok(settings)
+}
+
+fn settings(config: &Config) -> Result<Settings> {
+    let mut settings = read(Path::new(SYSTEM_CONFIG))?;
+
+    if let Some(path) = user_config(config) {
+        settings.merge(read(&path)?);
+    }
+
+    Ok(settings)
+}
+
+fn pacman_color(config: &Config) -> bool {
+    match pacmanconf::Config::with_opts(None, config.config.as_deref(), None) {
+        Ok(pacconf) => pacconf.color,
+        Err(_) => true,
+    }
+}
+
+// --color, then our own config, then NO_COLOR, then pacman.conf's Color
+fn color_when(config: &Config, settings: &Settings) -> String {
+    if let Some(ref when) = config.color_when {
+        return when.clone();
+    }
+    if let Some(ref when) = settings.color {
+        return when.clone();
+    }
+    if env::var_os("NO_COLOR").filter(|v| !v.is_empty()).is_some() || !pacman_color(config) {
+        return "never".into();
+    }
+    "auto".into()
+}
+
+fn apply_theme(colors: &mut Colors, theme: &Theme) {
+    for (role, spec) in theme.roles().iter() {
+        let style = match spec.as_deref().map(parse_style) {
+            Some(Ok(style)) => style,
+            _ => continue,
+        };
+
+        match *role {
+            "bold" => colors.bold = style,
+            "error" => colors.error = style,
+            "prompt" => colors.prompt = style,
+            "info" => colors.info = style,
+            "pacnew" => colors.pacnew = style,
+            "pacsave" => colors.pacsave = style,
+            _ => colors.age = style,
+        }
+    }
+}
+
+pub fn load(config: &mut Config) -> Result<()> {
+    let settings = match settings(config) {
+        Ok(settings) => settings,
+        Err(err) => {
+            config.color = parse_color(&color_when(config, &Settings::default()));
+            return Err(err);
+        }
+    };
+
+    config.color = parse_color(&color_when(config, &settings));
+    if config.color.enabled {
+        apply_theme(&mut config.color, &settings.colors);
+    }
+
+    if config.diffprog.is_none() {
+        config.diffprog = settings.diffprog;
+    }
+    if config.mergeprog.is_none() {
+        config.mergeprog = settings.mergeprog;
+    }
+    if settings.sudoedit == Some(false) {
+        config.nosudoedit = true;
+    }
+    config.columns = settings.columns.unwrap_or_default();
+    if config.kinds.is_empty() {
+        config.kinds = settings.kinds.unwrap_or_default();
+    }
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use ansi_term::Color::{Blue, Green, Red};
+use std::ffi::OsString;
+
+use ansi_term::Color::{Blue, Green, Purple, Red, Yellow};
 use ansi_term::Style;
 use atty::Stream::Stdout;
+use glob::Pattern;
 use structopt::clap::{arg_enum, AppSettings};
 use structopt::StructOpt;
 
@@ -16,6 +19,15 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Symlink {
+        through,
+        replace,
+    }
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(
     rename_all = "kebab-case",
@@ -28,20 +40,38 @@ arg_enum! {
 pub struct Config {
     #[structopt(
         possible_values = &["never", "auto", "always"],
-        parse(from_str = parse_color),
         long = "color",
-        default_value = "auto",
         takes_value = true,
         require_equals = true,
         help = "specify when to enable color"
     )]
+    pub color_when: Option<String>,
+
+    #[structopt(skip)]
     pub color: Colors,
 
-    #[structopt(long = "dbpath", short = "b", help = "the dbpath to use")]
-    pub dbpath: Option<String>,
+    #[structopt(
+        long = "dbpath",
+        short = "b",
+        number_of_values = 1,
+        help = "the dbpath to use, given once per --root"
+    )]
+    pub dbpath: Vec<String>,
+
+    #[structopt(
+        long = "root",
+        short = "r",
+        number_of_values = 1,
+        help = "the root dir to use, may be given multiple times"
+    )]
+    pub root: Vec<String>,
 
-    #[structopt(long = "root", short = "r", help = "the root dir to use")]
-    pub root: Option<String>,
+    #[structopt(
+        long = "machines",
+        short = "m",
+        help = "manage the roots of all machines in /var/lib/machines"
+    )]
+    pub machines: bool,
 
     #[structopt(long = "config", short = "c", help = "the pacman.conf to use")]
     pub config: Option<String>,
@@ -60,9 +90,73 @@ pub struct Config {
     )]
     pub output: bool,
 
-    #[structopt(long = "show hidden errors", short = "v", help = "")]
+    #[structopt(
+        long = "check",
+        help = "print a monitoring plugin status line and exit with its status"
+    )]
+    pub check: bool,
+
+    #[structopt(
+        long = "warning-count",
+        default_value = "1",
+        help = "number of pacfiles at which --check warns"
+    )]
+    pub warning_count: usize,
+
+    #[structopt(
+        long = "critical-count",
+        help = "number of pacfiles at which --check is critical"
+    )]
+    pub critical_count: Option<usize>,
+
+    #[structopt(
+        long = "warning-age",
+        help = "age in days of the oldest pacfile at which --check warns, also highlights old pacfiles in the table"
+    )]
+    pub warning_age: Option<u64>,
+
+    #[structopt(
+        long = "critical-age",
+        help = "age in days of the oldest pacfile at which --check is critical"
+    )]
+    pub critical_age: Option<u64>,
+
+    #[structopt(
+        long = "prometheus",
+        help = "write pacfile metrics to a node_exporter textfile and exit"
+    )]
+    pub prometheus: Option<String>,
+
+    #[structopt(
+        long = "watch",
+        short = "w",
+        help = "keep running and report new pacfiles as they appear"
+    )]
+    pub watch: bool,
+
+    #[structopt(
+        long = "notify",
+        help = "command to run through sh when --watch finds a new pacfile"
+    )]
+    pub notify: Option<String>,
+
+    #[structopt(long = "verbose", short = "v", help = "show hidden errors")]
     pub verbose: bool,
 
+    #[structopt(
+        long = "tui",
+        conflicts_with_all = &["plan", "action", "output"],
+        help = "pick and manage pacfiles in a full screen list with a diff preview"
+    )]
+    pub tui: bool,
+
+    #[structopt(
+        long = "plan",
+        conflicts_with = "action",
+        help = "choose an action for every pacfile at once in an editor, like git rebase -i"
+    )]
+    pub plan: bool,
+
     #[structopt(
         possible_values = &Action::variants(),
         long = "action",
@@ -71,6 +165,14 @@ pub struct Config {
     )]
     pub action: Option<Action>,
 
+    #[structopt(
+        possible_values = &Symlink::variants(),
+        long = "symlinks",
+        takes_value = true,
+        help = "whether overwriting a symlinked file writes through the link or replaces it"
+    )]
+    pub symlinks: Option<Symlink>,
+
     #[structopt(
         env = "DIFFPROG",
         long = "diffprog",
@@ -79,6 +181,13 @@ pub struct Config {
     )]
     pub diffprog: Option<String>,
 
+    #[structopt(
+        env = "MERGEPROG",
+        long = "mergeprog",
+        help = "merge program to use, the pacfile is removed if it exits successfully"
+    )]
+    pub mergeprog: Option<String>,
+
     #[structopt(
         long = "nosudoedit",
         help = "don't use sudo -e to open the editor under your user account"
@@ -93,10 +202,110 @@ pub struct Config {
     )]
     pub sudouser: Option<String>,
 
-    pub targets: Vec<String>,
+    #[structopt(
+        long = "nogit",
+        help = "don't commit changes to an /etc git or etckeeper repository"
+    )]
+    pub nogit: bool,
+
+    #[structopt(
+        long = "reload-units",
+        help = "reload or restart services affected by overwritten files without asking"
+    )]
+    pub reload_units: bool,
+
+    #[structopt(
+        long = "pre-hook",
+        number_of_values = 1,
+        help = "command to run through sh before removing or overwriting a pacfile"
+    )]
+    pub pre_hook: Vec<String>,
+
+    #[structopt(
+        long = "post-hook",
+        number_of_values = 1,
+        help = "command to run through sh after removing or overwriting a pacfile"
+    )]
+    pub post_hook: Vec<String>,
+
+    #[structopt(skip)]
+    pub columns: Vec<String>,
+
+    #[structopt(
+        possible_values = &["pacnew", "pacsave"],
+        long = "kind",
+        number_of_values = 1,
+        help = "only manage pacfiles of this kind"
+    )]
+    pub kinds: Vec<String>,
+
+    #[structopt(
+        long = "repo",
+        use_delimiter = true,
+        number_of_values = 1,
+        help = "only manage files of packages from these repos, eg: core,extra"
+    )]
+    pub repos: Vec<String>,
+
+    #[structopt(
+        long = "group",
+        use_delimiter = true,
+        number_of_values = 1,
+        help = "only manage files of packages in these groups"
+    )]
+    pub groups: Vec<String>,
+
+    #[structopt(
+        long = "explicit",
+        conflicts_with = "deps",
+        help = "only manage files of explicitly installed packages"
+    )]
+    pub explicit: bool,
+
+    #[structopt(
+        long = "deps",
+        help = "only manage files of packages installed as dependencies"
+    )]
+    pub deps: bool,
+
+    #[structopt(
+        long = "select",
+        help = "select pacfiles with the menu's selection syntax instead of prompting"
+    )]
+    pub select: Option<String>,
+
+    #[structopt(
+        long = "path",
+        number_of_values = 1,
+        parse(try_from_str = Pattern::new),
+        help = "only manage files matching this glob, may be given multiple times"
+    )]
+    pub paths: Vec<Pattern>,
+
+    #[structopt(
+        long = "exclude-package",
+        number_of_values = 1,
+        parse(try_from_str = Pattern::new),
+        help = "skip files owned by packages matching this glob"
+    )]
+    pub exclude_packages: Vec<Pattern>,
+
+    #[structopt(
+        long = "exclude-path",
+        number_of_values = 1,
+        parse(try_from_str = Pattern::new),
+        help = "skip files matching this glob"
+    )]
+    pub exclude_paths: Vec<Pattern>,
+
+    #[structopt(
+        parse(from_os_str),
+        help = "packages or files to manage, - reads them from stdin"
+    )]
+    pub targets: Vec<OsString>,
 }
 
-fn parse_color(s: &str) -> Colors {
+pub fn parse_color(s: &str) -> Colors {
     match s {
         "auto" if atty::is(Stdout) => Colors::new(),
         "always" => Colors::new(),
@@ -106,19 +315,27 @@ fn parse_color(s: &str) -> Colors {
 
 #[derive(Default, Debug)]
 pub struct Colors {
+    pub enabled: bool,
     pub bold: Style,
     pub error: Style,
     pub prompt: Style,
     pub info: Style,
+    pub pacnew: Style,
+    pub pacsave: Style,
+    pub age: Style,
 }
 
 impl Colors {
     pub fn new() -> Colors {
         Colors {
+            enabled: true,
             bold: Style::new().bold(),
             error: Style::new().fg(Red),
             prompt: Style::new().fg(Blue),
             info: Style::new().fg(Green),
+            pacnew: Style::new().fg(Yellow),
+            pacsave: Style::new().fg(Purple),
+            age: Style::new().fg(Red),
         }
     }
 }
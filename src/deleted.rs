@@ -0,0 +1,39 @@
+use crate::error::{Error, Result};
+
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "var/lib/pacdiffviewer/deleted";
+
+pub fn load(root: &Path) -> Vec<PathBuf> {
+    match fs::read(root.join(STATE_FILE)) {
+        Ok(data) => data
+            .split(|&c| c == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| root.join(OsStr::from_bytes(line)))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn record(root: &Path, file: &Path) -> Result<()> {
+    let state = root.join(STATE_FILE);
+    let relative = file.strip_prefix(root).unwrap_or(file);
+
+    if let Some(dir) = state.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::File("create directory", dir.into(), e))?;
+    }
+
+    let mut line = relative.as_os_str().as_bytes().to_vec();
+    line.push(b'\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&state)
+        .and_then(|mut f| f.write_all(&line))
+        .map_err(|e| Error::File("write", state, e))
+}
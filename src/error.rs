@@ -1,3 +1,6 @@
+use crate::util::escape_path;
+
+use std::path::PathBuf;
 use std::{fmt, io};
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -7,7 +10,14 @@ pub enum Error {
     AlpmInit(alpm::Error, String, String),
     Alpm(alpm::Error),
     Pacmanconf(pacmanconf::Error),
+    Conffile(PathBuf, String),
+    Select(String),
+    Args(String),
+    Target(String),
+    Hook(&'static str, String, Box<Error>),
     Io(io::Error),
+    File(&'static str, PathBuf, io::Error),
+    Rename(PathBuf, PathBuf, io::Error),
     CommandNonZero(String, Vec<String>, Option<i32>),
     CommandFailed(String, Vec<String>, io::Error),
 }
@@ -22,7 +32,25 @@ impl fmt::Display for Error {
             ),
             Error::Alpm(e) => e.fmt(fmt),
             Error::Pacmanconf(e) => e.fmt(fmt),
+            Error::Conffile(path, e) => {
+                write!(fmt, "invalid config '{}': {}", escape_path(path), e)
+            }
+            Error::Select(e) => write!(fmt, "--select: {}", e),
+            Error::Args(e) => e.fmt(fmt),
+            Error::Target(e) => e.fmt(fmt),
+            Error::Hook("pre", action, e) => write!(fmt, "pre-hook vetoed {}: {}", action, e),
+            Error::Hook(_, action, e) => write!(fmt, "post-hook for {} failed: {}", action, e),
             Error::Io(e) => e.fmt(fmt),
+            Error::File(op, path, e) => {
+                write!(fmt, "failed to {} '{}': {}", op, escape_path(path), e)
+            }
+            Error::Rename(from, to, e) => write!(
+                fmt,
+                "failed to move '{}' to '{}': {}",
+                escape_path(from),
+                escape_path(to),
+                e
+            ),
             Error::CommandNonZero(bin, args, exit) => {
                 if let Some(exit) = exit {
                     write!(
@@ -50,6 +78,24 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+impl Error {
+    pub fn operation(&self) -> &str {
+        match self {
+            Error::AlpmInit(..) | Error::Alpm(_) => "alpm",
+            Error::Pacmanconf(_) => "pacman.conf",
+            Error::Conffile(..) => "config",
+            Error::Select(_) => "select",
+            Error::Args(_) => "arguments",
+            Error::Target(_) => "target",
+            Error::Hook(..) => "hook",
+            Error::Io(_) => "io",
+            Error::File(op, _, _) => op,
+            Error::Rename(..) => "move",
+            Error::CommandNonZero(..) | Error::CommandFailed(..) => "run command",
+        }
+    }
+}
+
 impl From<alpm::Error> for Error {
     fn from(err: alpm::Error) -> Self {
         Error::Alpm(err)
@@ -0,0 +1,126 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::pacdiff::{Backup, Outcome};
+use crate::util::escape_path;
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use which::which;
+
+pub struct Repo {
+    dir: PathBuf,
+}
+
+impl Repo {
+    pub fn find(config: &Config, root: &Path) -> Option<Repo> {
+        let dir = root.join("etc");
+
+        if config.nogit || !dir.join(".git").exists() || which("git").is_err() {
+            return None;
+        }
+
+        Some(Repo { dir })
+    }
+
+    fn run(&self, bin: &str, args: Vec<OsString>) -> Result<()> {
+        let exit = Command::new(bin).args(&args).status();
+        let args = || args.iter().map(|s| escape_path(Path::new(s))).collect();
+
+        match exit {
+            Ok(exit) if exit.success() => Ok(()),
+            Ok(exit) => Err(Error::CommandNonZero(bin.into(), args(), exit.code())),
+            Err(err) => Err(Error::CommandFailed(bin.into(), args(), err)),
+        }
+    }
+
+    fn git(&self, args: &[&str]) -> Vec<OsString> {
+        let mut out = vec!["-C".into(), self.dir.clone().into()];
+        out.extend(args.iter().map(|a| a.into()));
+        out
+    }
+
+    fn tracked(&self, path: &Path) -> bool {
+        let mut args = self.git(&["ls-files", "--error-unmatch", "--"]);
+        args.push(path.into());
+
+        Command::new("git")
+            .args(&args)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    fn staged(&self, paths: &[PathBuf]) -> bool {
+        let mut args = self.git(&["diff", "--cached", "--quiet", "--"]);
+        args.extend(paths.iter().map(|p| p.into()));
+
+        Command::new("git")
+            .args(&args)
+            .status()
+            .map(|s| !s.success())
+            .unwrap_or(true)
+    }
+
+    pub fn log(&self, file: &Path) -> Result<()> {
+        let mut args = self.git(&["log", "-p", "--follow", "--"]);
+        args.push(file.into());
+        self.run("git", args)
+    }
+
+    pub fn commit(&self, changes: &[(&Backup, Outcome)]) -> Result<()> {
+        let mut packages = Vec::new();
+        let mut paths = Vec::new();
+        let mut message = String::new();
+
+        for (backup, outcome) in changes {
+            if !packages.contains(&backup.package.as_str()) {
+                packages.push(backup.package.as_str());
+            }
+
+            message.push_str(&format!(
+                "\n{} {} {}",
+                outcome,
+                backup.package,
+                escape_path(&backup.file)
+            ));
+
+            for path in backup.pacfiles.iter().chain(Some(&backup.file)) {
+                if path.starts_with(&self.dir) && (path.exists() || self.tracked(path)) {
+                    paths.push(path.clone());
+                }
+            }
+        }
+
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let message = format!("pacdiffviewer: {}\n{}", packages.join(", "), message);
+
+        if self.dir.join(".etckeeper").exists() && which("etckeeper").is_ok() {
+            let args = vec![
+                "commit".into(),
+                "-d".into(),
+                self.dir.clone().into(),
+                message.into(),
+            ];
+            return self.run("etckeeper", args);
+        }
+
+        let mut args = self.git(&["add", "-A", "--"]);
+        args.extend(paths.iter().map(|p| p.into()));
+        self.run("git", args)?;
+
+        if !self.staged(&paths) {
+            return Ok(());
+        }
+
+        let mut args = self.git(&["commit", "-q", "-m"]);
+        args.push(message.into());
+        args.push("--".into());
+        args.extend(paths.iter().map(|p| p.into()));
+        self.run("git", args)
+    }
+}
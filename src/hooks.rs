@@ -0,0 +1,49 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::pacdiff::{report, Backup};
+
+use std::ffi::OsStr;
+use std::process::Command;
+
+pub fn shell(cmd: &str, backup: &Backup, env: &[(&str, &OsStr)]) -> Result<()> {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    backup.set_env(&mut command);
+    command.envs(env.iter().copied());
+
+    let args = vec!["-c".to_string(), cmd.to_string()];
+    let exit = command
+        .status()
+        .map_err(|err| Error::CommandFailed("sh".into(), args.clone(), err))?;
+
+    if !exit.success() {
+        return Err(Error::CommandNonZero("sh".into(), args, exit.code()));
+    }
+
+    Ok(())
+}
+
+pub fn pre(config: &Config, backup: &Backup, action: &str, errors: &mut Vec<Error>) -> bool {
+    let env = [("PACDIFF_ACTION", OsStr::new(action))];
+
+    for hook in &config.pre_hook {
+        if let Err(err) = shell(hook, backup, &env) {
+            let err = Error::Hook("pre", action.into(), Box::new(err));
+            report(config, errors, err);
+            return false;
+        }
+    }
+
+    true
+}
+
+pub fn post(config: &Config, backup: &Backup, action: &str, errors: &mut Vec<Error>) {
+    let env = [("PACDIFF_ACTION", OsStr::new(action))];
+
+    for hook in &config.post_hook {
+        if let Err(err) = shell(hook, backup, &env) {
+            let err = Error::Hook("post", action.into(), Box::new(err));
+            report(config, errors, err);
+        }
+    }
+}
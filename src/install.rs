@@ -0,0 +1,158 @@
+use crate::error::{Error, Result};
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::{chown, symlink, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::process;
+
+fn unescape_mountinfo(field: &[u8]) -> PathBuf {
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+
+    while i < field.len() {
+        let octal = field.get(i + 1..i + 4).and_then(|o| {
+            let o = std::str::from_utf8(o).ok()?;
+            u8::from_str_radix(o, 8).ok()
+        });
+
+        match octal {
+            Some(c) if field[i] == b'\\' => {
+                out.push(c);
+                i += 4;
+            }
+            _ => {
+                out.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+
+    OsString::from_vec(out).into()
+}
+
+fn is_mount_point(path: &Path) -> bool {
+    let path = match (path.parent().map(fs::canonicalize), path.file_name()) {
+        (Some(Ok(parent)), Some(name)) => parent.join(name),
+        _ => return false,
+    };
+
+    match path.symlink_metadata() {
+        Ok(metadata) if !metadata.file_type().is_symlink() => (),
+        _ => return false,
+    }
+
+    if let (Ok(file), Some(Ok(parent))) = (path.metadata(), path.parent().map(|p| p.metadata())) {
+        if file.dev() != parent.dev() {
+            return true;
+        }
+    }
+
+    let mountinfo = match fs::read("/proc/self/mountinfo") {
+        Ok(mountinfo) => mountinfo,
+        Err(_) => return false,
+    };
+
+    mountinfo
+        .split(|&c| c == b'\n')
+        .filter_map(|line| line.split(|&c| c == b' ').nth(4))
+        .any(|mount| unescape_mountinfo(mount) == path)
+}
+
+fn write_in_place(src: &Path, dest: &Path) -> Result<()> {
+    let mut input = File::open(src).map_err(|e| Error::File("open", src.into(), e))?;
+    let mut output = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(dest)
+        .map_err(|e| Error::File("open", dest.into(), e))?;
+
+    io::copy(&mut input, &mut output).map_err(|e| Error::File("write", dest.into(), e))?;
+    output
+        .sync_all()
+        .map_err(|e| Error::File("sync", dest.into(), e))?;
+    fs::remove_file(src).map_err(|e| Error::File("remove", src.into(), e))
+}
+
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(|e| Error::File("sync", dir.into(), e))
+}
+
+fn copy_to_temp(src: &Path, tmp: &Path, dest: &Path) -> Result<()> {
+    let metadata = src
+        .metadata()
+        .map_err(|e| Error::File("stat", src.into(), e))?;
+    fs::copy(src, tmp).map_err(|e| Error::File("copy", src.into(), e))?;
+    chown(tmp, Some(metadata.uid()), Some(metadata.gid()))
+        .map_err(|e| Error::File("chown", tmp.into(), e))?;
+    File::open(tmp)
+        .and_then(|f| f.sync_all())
+        .map_err(|e| Error::File("sync", tmp.into(), e))?;
+    fs::rename(tmp, dest).map_err(|e| Error::Rename(tmp.into(), dest.into(), e))
+}
+
+fn temp_path(dest: &Path, dir: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(format!(".pacdiffviewer-{}", process::id()));
+    dir.join(name)
+}
+
+fn copy_and_rename(src: &Path, dest: &Path, dir: &Path) -> Result<()> {
+    let tmp = temp_path(dest, dir);
+
+    if let Err(err) = copy_to_temp(src, &tmp, dest) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+
+    sync_dir(dir)?;
+    fs::remove_file(src).map_err(|e| Error::File("remove", src.into(), e))
+}
+
+fn move_link(src: &Path, dest: &Path, dir: &Path) -> Result<()> {
+    let target = fs::read_link(src).map_err(|e| Error::File("read link", src.into(), e))?;
+    let tmp = temp_path(dest, dir);
+
+    symlink(&target, &tmp).map_err(|e| Error::File("create link", tmp.clone(), e))?;
+    if let Err(err) = fs::rename(&tmp, dest) {
+        let _ = fs::remove_file(&tmp);
+        return Err(Error::Rename(tmp, dest.into(), err));
+    }
+
+    sync_dir(dir)?;
+    fs::remove_file(src).map_err(|e| Error::File("remove", src.into(), e))
+}
+
+pub fn install(src: &Path, dest: &Path) -> Result<()> {
+    let dir = match dest.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let metadata = src
+        .symlink_metadata()
+        .map_err(|e| Error::File("stat", src.into(), e))?;
+
+    if metadata.file_type().is_symlink() {
+        return move_link(src, dest, dir);
+    }
+
+    if is_mount_point(dest) {
+        return write_in_place(src, dest);
+    }
+
+    File::open(src)
+        .and_then(|f| f.sync_all())
+        .map_err(|e| Error::File("sync", src.into(), e))?;
+
+    match fs::rename(src, dest) {
+        Ok(()) => sync_dir(dir),
+        Err(err) if err.raw_os_error() == Some(libc::EXDEV) => copy_and_rename(src, dest, dir),
+        Err(err) => Err(Error::Rename(src.into(), dest.into(), err)),
+    }
+}
@@ -1,20 +1,43 @@
+mod binary;
+mod check;
+mod conffile;
 mod config;
+mod deleted;
 mod error;
+mod git;
+mod hooks;
+mod install;
 mod pacdiff;
+mod plan;
+mod prometheus;
+mod select;
+mod tui;
+mod units;
+mod util;
+mod watch;
 
 use crate::config::Config;
-use crate::pacdiff::run;
+use crate::pacdiff::{read_targets, run};
 
 use std::process;
 
 use structopt::StructOpt;
 
 fn main() {
-    let config = Config::from_args();
-    let res = run(&config);
+    let mut config = Config::from_args();
+    let res = conffile::load(&mut config)
+        .and_then(|_| read_targets(&mut config))
+        .and_then(|_| run(&config));
 
-    if let Err(err) = res {
-        eprintln!("{} {}", config.color.error.paint("error:"), err);
-        process::exit(1);
+    match res {
+        Ok(code) => process::exit(code),
+        Err(err) if config.check => {
+            println!("UNKNOWN: {}", err);
+            process::exit(check::UNKNOWN);
+        }
+        Err(err) => {
+            eprintln!("{} {}", config.color.error.paint("error:"), err);
+            process::exit(1);
+        }
     }
 }
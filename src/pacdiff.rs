@@ -1,69 +1,601 @@
-use crate::config::Config;
+use crate::binary;
+use crate::check::check;
+use crate::config::{Config, Symlink};
+use crate::deleted;
 use crate::error::{Error, Result};
-
+use crate::git::Repo;
+use crate::hooks;
+use crate::install::install;
+use crate::plan::plan;
+use crate::prometheus;
+use crate::select::{path_matches, select};
+use crate::tui;
+use crate::units::suggest_restarts;
+use crate::util::escape_path;
+use crate::watch::watch;
+
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::DirBuilder;
 use std::io::BufRead;
 use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{self, Command};
+use std::time::{Duration, SystemTime};
 use std::{fs, io};
 
-use alpm::Alpm;
+use alpm::{Alpm, PackageReason, SigLevel};
+use ansi_term::Style;
+use atty::Stream;
 use chrono::{DateTime, Utc};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use which::which;
 
-#[derive(PartialEq)]
-enum Kind {
+const OLD_AGE_DAYS: u64 = 30;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Kind {
     Pacsave,
     Pacnew,
 }
 
-struct Backup {
-    package: String,
-    file: PathBuf,
-    pacfiles: Vec<PathBuf>,
-    kind: Kind,
+impl fmt::Display for Kind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Pacsave => fmt.write_str("pacsave"),
+            Kind::Pacnew => fmt.write_str("pacnew"),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Outcome {
+    Skipped,
+    Removed,
+    Overwritten,
+    Merged,
+    Failed,
+    Quit,
+    Back,
+    Next,
+    Jump(usize),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Skipped => fmt.write_str("skip"),
+            Outcome::Removed => fmt.write_str("remove"),
+            Outcome::Overwritten => fmt.write_str("overwrite"),
+            Outcome::Merged => fmt.write_str("merge"),
+            Outcome::Failed => fmt.write_str("fail"),
+            Outcome::Quit => fmt.write_str("quit"),
+            Outcome::Back => fmt.write_str("back"),
+            Outcome::Next => fmt.write_str("next"),
+            Outcome::Jump(n) => write!(fmt, "jump to {}", n),
+        }
+    }
 }
 
-pub fn run(config: &Config) -> Result<()> {
-    let mut pacconf =
-        pacmanconf::Config::with_opts(None, config.config.as_deref(), config.root.as_deref())?;
-    if let Some(ref db_path) = config.dbpath {
+pub struct Backup {
+    pub root: PathBuf,
+    pub package: String,
+    pub file: PathBuf,
+    pub pacfiles: Vec<PathBuf>,
+    pub kind: Kind,
+    pub missing: bool,
+}
+
+fn init_alpm(
+    config: &Config,
+    root: Option<&str>,
+    pacman_conf: Option<&str>,
+    dbpath: Option<&String>,
+) -> Result<Alpm> {
+    let pacman_conf = config.config.as_deref().or(pacman_conf);
+    let mut pacconf = pacmanconf::Config::with_opts(None, pacman_conf, root)?;
+    if let Some(db_path) = dbpath {
         pacconf.db_path = db_path.clone();
     }
+    let repos = std::mem::take(&mut pacconf.repos);
     let alpm = Alpm::new(&*pacconf.root_dir, &*pacconf.db_path)
         .map_err(|e| Error::AlpmInit(e, pacconf.root_dir, pacconf.db_path))?;
 
-    let mut backups = get_backups(&config, &alpm)?;
+    if !config.repos.is_empty() {
+        for repo in repos {
+            alpm.register_syncdb(repo.name, SigLevel::USE_DEFAULT)?;
+        }
+    }
+
+    Ok(alpm)
+}
+
+fn machines(config: &Config, errors: &mut Vec<Error>) -> Result<Vec<PathBuf>> {
+    let mut machines = Vec::new();
+
+    let dir = Path::new("/var/lib/machines");
+    let read = fs::read_dir(dir).map_err(|e| Error::File("read directory", dir.into(), e))?;
+
+    for entry in read {
+        let path = entry
+            .map_err(|e| Error::File("read directory", dir.into(), e))?
+            .path();
+        if !path.join("var/lib/pacman/local").is_dir() {
+            continue;
+        }
+
+        // pacman.conf and alpm only take roots as strings
+        if path.to_str().is_none() {
+            let err = io::Error::new(io::ErrorKind::InvalidData, "path is not valid UTF-8");
+            report(config, errors, Error::File("use machine", path, err));
+            continue;
+        }
+
+        machines.push(path);
+    }
+
+    machines.sort();
+    Ok(machines)
+}
+
+fn init_alpms(config: &Config, errors: &mut Vec<Error>) -> Result<Vec<Alpm>> {
+    let mut alpms = Vec::new();
+
+    // each --dbpath belongs to the --root at the same position
+    let dbpaths = if config.root.is_empty() && !config.machines {
+        1
+    } else {
+        config.root.len()
+    };
+    if !config.dbpath.is_empty() && config.dbpath.len() != dbpaths {
+        return Err(Error::Args(format!(
+            "expected one --dbpath per --root but got {} for {}",
+            config.dbpath.len(),
+            dbpaths,
+        )));
+    }
+
+    if config.root.is_empty() && !config.machines {
+        alpms.push(init_alpm(config, None, None, config.dbpath.first())?);
+    }
+
+    for (n, root) in config.root.iter().enumerate() {
+        alpms.push(init_alpm(config, Some(root), None, config.dbpath.get(n))?);
+    }
+
+    if config.machines {
+        for machine in machines(config, errors)? {
+            let root = machine.to_string_lossy();
+            let pacman_conf = machine.join("etc/pacman.conf");
+            let pacman_conf = Some(pacman_conf)
+                .filter(|p| p.exists())
+                .map(|p| p.to_string_lossy().into_owned());
+
+            // one broken machine should not stop the others from being managed
+            match init_alpm(config, Some(&root), pacman_conf.as_deref(), None) {
+                Ok(alpm) => alpms.push(alpm),
+                Err(err) => report(config, errors, err),
+            }
+        }
+    }
+
+    Ok(alpms)
+}
+
+pub fn run(config: &Config) -> Result<i32> {
+    let mut errors = Vec::new();
+    let alpms = init_alpms(config, &mut errors)?;
+
+    if config.watch {
+        watch(config, &alpms)?;
+        return Ok(0);
+    }
+
+    let mut all = Vec::new();
+    for alpm in &alpms {
+        all.extend(get_backups(config, alpm, &mut errors));
+    }
+
+    if let Some(ref path) = config.prometheus {
+        prometheus::write(path, &all)?;
+    }
+
+    if config.check {
+        return Ok(check(config, &all, &errors));
+    }
+
+    if config.prometheus.is_some() {
+        print_errors(config, &errors);
+        return Ok(0);
+    }
+
+    let mut backups = all.iter().collect::<Vec<_>>();
+    let mut presets = Vec::new();
+
+    if let Some(ref selection) = config.select {
+        backups = select(&backups, selection, false).map_err(Error::Select)?;
+    }
+
+    if config.plan && !config.output && !backups.is_empty() {
+        let steps = plan(config, &backups, &mut errors)?;
+        backups = steps.iter().map(|(backup, _)| *backup).collect();
+        presets = steps.into_iter().map(|(_, action)| action).collect();
+    } else if !config.all && !config.tui && config.select.is_none() && !backups.is_empty() {
+        print_backups(config, &all, &mut errors);
+
+        loop {
+            let prompt = "Files to manage (eg: all, 1-3 ^2, openssh, /etc/ssh/*, pacsave,>30d): ";
+            let input = readline(config, prompt)?;
 
-    if !config.all && !backups.is_empty() {
-        print_backups(config, &backups);
-        let input =
-            readline(config, "Files to manage (eg: all, 1 2 3, 1-3 or ^4): ")?.to_lowercase();
-        backups = filter_backups(backups, &input);
+            match select(&backups, &input, true) {
+                Ok(selected) => {
+                    backups = selected;
+                    break;
+                }
+                Err(err) => eprintln!("{} {}", config.color.error.paint("error:"), err),
+            }
+        }
     }
 
+    let mut outcomes = Vec::new();
+
     if config.output {
+        let mut stdout = io::stdout();
         for backup in &backups {
             for file in &backup.pacfiles {
-                println!("{}", file.display());
+                stdout.write_all(file.as_os_str().as_bytes())?;
+                stdout.write_all(b"\n")?;
             }
         }
     } else {
-        for (n, backup) in backups.iter().enumerate() {
-            if backup.manage(config, n + 1, backups.len())? {
-                break;
+        let repos = alpms
+            .iter()
+            .map(|alpm| Repo::find(config, Path::new(alpm.root())))
+            .collect::<Vec<_>>();
+
+        let repo_for = |backup: &Backup| {
+            alpms
+                .iter()
+                .position(|alpm| Path::new(alpm.root()) == backup.root)
+                .and_then(|i| repos[i].as_ref())
+        };
+
+        if config.tui {
+            outcomes = tui::run(config, &backups, repo_for, &mut errors)?;
+        } else {
+            let interactive = config.action.is_none() && presets.is_empty();
+            let mut results = vec![None; backups.len()];
+            let mut order = (0..backups.len()).collect::<Vec<_>>();
+
+            while !order.is_empty() {
+                if !walk(
+                    config,
+                    &backups,
+                    &order,
+                    &presets,
+                    &repo_for,
+                    &mut errors,
+                    &mut results,
+                )? {
+                    break;
+                }
+
+                order = (0..backups.len())
+                    .filter(|&n| matches!(results[n], None | Some(Outcome::Skipped)))
+                    .collect();
+
+                if !interactive || order.is_empty() {
+                    break;
+                }
+
+                let prompt = format!(
+                    "You skipped {} file{}, review them again? [y/N] ",
+                    order.len(),
+                    if order.len() == 1 { "" } else { "s" }
+                );
+                if !readline(config, &prompt)?
+                    .trim()
+                    .to_lowercase()
+                    .starts_with('y')
+                {
+                    break;
+                }
             }
+
+            outcomes = backups
+                .iter()
+                .zip(results)
+                .filter_map(|(backup, outcome)| outcome.map(|o| (*backup, o)))
+                .collect();
         }
+
+        for (alpm, repo) in alpms.iter().zip(&repos) {
+            let root = Path::new(alpm.root());
+            let outcomes = outcomes
+                .iter()
+                .filter(|(backup, _)| backup.root == root)
+                .filter(|(_, outcome)| *outcome != Outcome::Skipped && *outcome != Outcome::Failed)
+                .copied()
+                .collect::<Vec<_>>();
+
+            if let Some(ref repo) = repo {
+                if let Err(err) = repo.commit(&outcomes) {
+                    errors.push(err);
+                }
+            }
+
+            let mut changed = Vec::new();
+            for (backup, outcome) in &outcomes {
+                let modified = *outcome == Outcome::Overwritten || *outcome == Outcome::Merged;
+                if modified && !changed.contains(&backup.package) {
+                    changed.push(backup.package.clone());
+                }
+            }
+
+            if let Err(err) = suggest_restarts(config, alpm, &changed) {
+                errors.push(err);
+            }
+        }
+
+        print_summary(config, &outcomes, &all);
     }
 
-    Ok(())
+    print_errors(config, &errors);
+
+    if outcomes
+        .iter()
+        .any(|(_, outcome)| *outcome == Outcome::Failed)
+    {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
 }
 
-fn readline(config: &Config, prompt: &str) -> Result<String> {
+// runs manage over the backups listed in order, returning false if the user quit
+fn walk<'a, 'r, F>(
+    config: &Config,
+    backups: &[&'a Backup],
+    order: &[usize],
+    presets: &[&str],
+    repo_for: &F,
+    errors: &mut Vec<Error>,
+    results: &mut [Option<Outcome>],
+) -> Result<bool>
+where
+    F: Fn(&Backup) -> Option<&'r Repo>,
+{
+    let mut pos = 0;
+
+    while let Some(&n) = order.get(pos) {
+        let backup = backups[n];
+        let preset = presets.get(n).copied();
+        let mut back = false;
+
+        match backup.manage(
+            config,
+            repo_for(backup),
+            errors,
+            pos + 1,
+            order.len(),
+            preset,
+        )? {
+            Outcome::Quit => return Ok(false),
+            Outcome::Back => {
+                pos -= 1;
+                back = true;
+            }
+            Outcome::Next => pos += 1,
+            Outcome::Jump(to) => pos = to - 1,
+            outcome => {
+                results[n] = Some(outcome);
+                pos += 1;
+            }
+        }
+
+        // files that were already dealt with have no pacfile left to go back to
+        while let Some(&n) = order.get(pos) {
+            match results[n] {
+                None | Some(Outcome::Skipped) | Some(Outcome::Failed) => break,
+                Some(_) if back && pos > 0 => pos -= 1,
+                Some(_) => {
+                    back = false;
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+fn print_summary(config: &Config, outcomes: &[(&Backup, Outcome)], backups: &[Backup]) {
+    const COLUMNS: [&str; 5] = ["Removed", "Overwritten", "Merged", "Skipped", "Failed"];
+
     let p = config.color.prompt;
     let b = config.color.bold;
-    print!("{} {}", p.paint("::"), b.paint(prompt));
-    io::stdout().flush()?;
+    let mut packages = Vec::<(&str, [usize; 5])>::new();
+    let mut total = [0; 5];
+
+    for (backup, outcome) in outcomes {
+        let column = match outcome {
+            Outcome::Removed => 0,
+            Outcome::Overwritten => 1,
+            Outcome::Merged => 2,
+            Outcome::Skipped => 3,
+            Outcome::Failed => 4,
+            Outcome::Quit | Outcome::Back | Outcome::Next | Outcome::Jump(_) => continue,
+        };
+
+        total[column] += 1;
+
+        match packages.iter_mut().find(|(p, _)| *p == backup.package) {
+            Some((_, counts)) => counts[column] += 1,
+            None => {
+                let mut counts = [0; 5];
+                counts[column] = 1;
+                packages.push((&backup.package, counts));
+            }
+        }
+    }
+
+    packages.sort();
+
+    let pending = backups
+        .iter()
+        .flat_map(|backup| &backup.pacfiles)
+        .filter(|file| file.symlink_metadata().is_ok())
+        .collect::<Vec<_>>();
+
+    if !packages.is_empty() {
+        let maxpkg = packages
+            .iter()
+            .map(|(p, _)| p.len())
+            .chain(Some("Package".len()))
+            .max()
+            .unwrap_or(0);
+
+        let row = |name: &str, counts: &[usize; 5]| {
+            let mut row = format!("{:pkg$}", name, pkg = maxpkg);
+            for (count, column) in counts.iter().zip(&COLUMNS) {
+                row.push_str(&format!("  {:>width$}", count, width = column.len()));
+            }
+            row
+        };
+
+        println!("\n{} {}", p.paint("::"), b.paint("Summary:"));
+        println!(
+            "{}",
+            b.paint(format!(
+                "{:pkg$}  {}",
+                "Package",
+                COLUMNS.join("  "),
+                pkg = maxpkg
+            ))
+        );
+        for (package, counts) in &packages {
+            println!("{}", row(package, counts));
+        }
+        if packages.len() > 1 {
+            println!("{}", b.paint(row("Total", &total)));
+        }
+    }
+
+    if !pending.is_empty() {
+        println!("\n{} {}", p.paint("::"), b.paint("Pending pacfiles:"));
+        for file in pending {
+            println!("    {}", escape_path(file));
+        }
+    }
+}
+
+fn print_errors(config: &Config, errors: &[Error]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    let e = config.color.error;
+    let b = config.color.bold;
+    let mut operations = Vec::new();
+
+    for err in errors {
+        if !operations.contains(&err.operation()) {
+            operations.push(err.operation());
+        }
+    }
+
+    eprintln!(
+        "\n{} {}",
+        e.paint("::"),
+        b.paint(format!("{} errors occurred:", errors.len()))
+    );
+
+    for operation in operations {
+        eprintln!("  {}:", b.paint(operation));
+        for err in errors.iter().filter(|err| err.operation() == operation) {
+            eprintln!("    {}", err);
+        }
+    }
+}
+
+pub fn mtime(file: &Path) -> io::Result<SystemTime> {
+    file.symlink_metadata()?.modified()
+}
+
+pub fn format_time(time: Option<SystemTime>) -> String {
+    match time {
+        Some(time) => DateTime::<Utc>::from(time).format("%c").to_string(),
+        None => "Unknown".to_string(),
+    }
+}
+
+pub fn run_editor(config: &Config, prog: &str, files: &[&Path], sudoedit: bool) -> Result<()> {
+    let bin;
+    let mut args = Vec::<OsString>::new();
+
+    if !sudoedit || config.nosudoedit || config.sudouser.is_none() {
+        let mut split = prog.split_whitespace();
+        bin = split.next().unwrap();
+        args.extend(split.map(|e| e.into()));
+        args.extend(files.iter().map(|f| f.into()));
+    } else {
+        let user = config.sudouser.as_deref().unwrap_or_default();
+        bin = "sudo";
+
+        args.push(format!("SUDO_EDITOR={}", prog).into());
+        args.push("-u".into());
+        args.push(user.into());
+        args.push("sudo".into());
+        args.push("-e".into());
+        args.extend(files.iter().map(|f| f.into()));
+    }
+
+    let mut command = Command::new(bin);
+    command.args(&args);
+
+    let exit = match command.spawn() {
+        Err(err) => {
+            return Err(Error::CommandFailed(
+                bin.to_string(),
+                args.iter().map(|s| escape_path(Path::new(s))).collect(),
+                err,
+            ));
+        }
+        Ok(o) => o,
+    }
+    .wait()?;
+
+    if !exit.success() {
+        return Err(Error::CommandNonZero(
+            bin.to_string(),
+            args.iter().map(|s| escape_path(Path::new(s))).collect(),
+            exit.code(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn private_temp_dir() -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("pacdiffviewer-{}", process::id()));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .map_err(|e| Error::File("create directory", dir.clone(), e))?;
+    Ok(dir)
+}
+
+pub fn report(config: &Config, errors: &mut Vec<Error>, err: Error) {
+    eprintln!("{} {}", config.color.error.paint("error:"), err);
+    errors.push(err);
+}
+
+fn read_input() -> Result<String> {
     let mut line = String::new();
     io::stdin().lock().read_line(&mut line)?;
     if !line.ends_with('\n') {
@@ -72,430 +604,1078 @@ fn readline(config: &Config, prompt: &str) -> Result<String> {
     Ok(line)
 }
 
+pub fn readline(config: &Config, prompt: &str) -> Result<String> {
+    let p = config.color.prompt;
+    let b = config.color.bold;
+    print!("{} {}", p.paint("::"), b.paint(prompt));
+    io::stdout().flush()?;
+    read_input()
+}
+
+fn read_key() -> Result<KeyEvent> {
+    enable_raw_mode()?;
+    let key = loop {
+        match event::read() {
+            Ok(Event::Key(key)) => break Ok(key),
+            Ok(_) => continue,
+            Err(err) => break Err(err),
+        }
+    };
+    disable_raw_mode()?;
+    Ok(key?)
+}
+
+fn print_choices_help(choices: &[(&str, &str)], total: usize) {
+    for (label, help) in choices {
+        let key = label.chars().nth(1).unwrap_or(' ').to_ascii_lowercase();
+        println!("    {}  {}", key, help);
+    }
+    if total > 1 {
+        println!("    1-{}  jump to a file", total);
+    }
+    println!("    ?  show this help");
+}
+
+// answers with a single key press on a terminal and falls back to reading a line
+// when input is piped, so scripted answers keep working
+fn read_choice(config: &Config, choices: &[(&str, &str)], default: &str) -> Result<String> {
+    let p = config.color.prompt;
+    let b = config.color.bold;
+    let mut prompt = String::new();
+
+    for (label, _) in choices {
+        let style = if *label == default && config.color.enabled {
+            config.color.info.bold()
+        } else {
+            b
+        };
+        prompt.push_str(&style.paint(*label).to_string());
+        prompt.push(' ');
+    }
+    prompt.push_str(&b.paint("(? for help): ").to_string());
+
+    print!("{} {}", p.paint("::"), prompt);
+    io::stdout().flush()?;
+
+    if !atty::is(Stream::Stdin) || !atty::is(Stream::Stdout) {
+        return read_input();
+    }
+
+    // keys other than plain characters are ignored, so a stray arrow key can't act
+    let input = loop {
+        let key = read_key()?;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let plain = (key.modifiers - KeyModifiers::SHIFT).is_empty();
+
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => break "q".to_string(),
+            KeyCode::Char(c) if plain && c.is_ascii_digit() => {
+                print!("{}", c);
+                io::stdout().flush()?;
+                let rest = read_input()?;
+                return Ok(format!("{}{}", c, rest));
+            }
+            KeyCode::Char(c) if plain => break c.to_string(),
+            KeyCode::Enter => break String::new(),
+            _ => continue,
+        }
+    };
+
+    println!("{}", input);
+    Ok(input)
+}
+
 impl Backup {
+    pub fn set_env(&self, command: &mut Command) {
+        let mut pacfiles = OsString::new();
+
+        for (n, file) in self.pacfiles.iter().enumerate() {
+            if n != 0 {
+                pacfiles.push("\n");
+            }
+            pacfiles.push(file);
+        }
+
+        command
+            .env("PACDIFF_ROOT", &self.root)
+            .env("PACDIFF_PACKAGE", &self.package)
+            .env("PACDIFF_FILE", &self.file)
+            .env("PACDIFF_KIND", self.kind.to_string())
+            .env("PACDIFF_PACFILES", pacfiles);
+    }
+
     fn format_pacfiles(&self) -> String {
         if self.pacfiles.len() == 1 {
-            return self.pacfiles[0].to_string_lossy().into_owned();
+            return escape_path(&self.pacfiles[0]);
         }
 
-        let orig_file = self.file.to_string_lossy();
-        let mut pacfiles = format!("{}{{", orig_file);
+        let orig_file = self.file.as_os_str().as_bytes();
+        let kind: &[u8] = match self.kind {
+            Kind::Pacnew => b".pacnew",
+            Kind::Pacsave => b".pacsave",
+        };
+        let mut suffixes = Vec::new();
 
-        let mut iter = self.pacfiles.iter();
-        iter.next_back();
+        for file in &self.pacfiles {
+            let file = file.as_os_str().as_bytes();
+            let file = file.strip_prefix(orig_file).unwrap_or(file);
+            let mut file = file.strip_prefix(kind).unwrap_or(file);
 
-        for file in iter {
-            let file = file.to_string_lossy();
-            let file = file.trim_start_matches(&*orig_file);
-            let file = match self.kind {
-                Kind::Pacnew => file.trim_start_matches(".pacnew"),
-                Kind::Pacsave => file.trim_start_matches(".pacsave"),
-            };
+            while let Some(rest) = file.strip_prefix(b".") {
+                file = rest;
+            }
 
             if !file.is_empty() {
-                pacfiles.push_str(file.trim_start_matches('.'));
-                pacfiles.push_str(", ");
+                suffixes.push(escape_path(Path::new(OsStr::from_bytes(file))));
             }
         }
 
-        let file = self.pacfiles.last().unwrap().to_string_lossy();
-        let file = file.trim_start_matches(&*orig_file);
-        let file = match self.kind {
-            Kind::Pacnew => file.trim_start_matches(".pacnew"),
-            Kind::Pacsave => file.trim_start_matches(".pacsave"),
-        };
+        format!("{}{{{}}}", escape_path(&self.file), suffixes.join(", "))
+    }
 
-        if !file.is_empty() {
-            pacfiles.push_str(file.trim_start_matches('.'));
+    pub fn live_file(&self) -> &Path {
+        if self.missing {
+            Path::new("/dev/null")
+        } else {
+            &self.file
         }
-
-        pacfiles.push('}');
-        pacfiles
     }
 
-    fn diffprog(&self, config: &Config, diffprog: &str) -> Result<()> {
-        use std::ffi::OsString;
+    pub fn format_file(&self, selected: usize) -> String {
+        let mut file = self.format_pacfiles() + &self.format_links(selected);
+        if self.missing {
+            file.push_str(" (original missing)");
+        }
+        file
+    }
 
-        let bin;
-        let mut args = Vec::<OsString>::new();
+    fn format_links(&self, selected: usize) -> String {
+        let mut links = String::new();
 
-        if config.nosudoedit || config.sudouser.is_none() {
-            let mut split = diffprog.split_whitespace();
-            bin = split.next().unwrap();
-            args.extend(split.map(|e| e.into()));
-            args.push(self.file.clone().into());
-            args.extend(self.pacfiles.iter().map(|p| p.into()));
-        } else {
-            let user = config.sudouser.as_ref().unwrap();
-            bin = "sudo";
+        if let Ok(target) = fs::read_link(&self.file) {
+            links.push_str(&format!(" -> {}", escape_path(&target)));
+        }
 
-            args.push(format!("SUDO_EDITOR={}", diffprog).into());
-            args.push("-u".into());
-            args.push(user.into());
-            args.push("sudo".into());
-            args.push("-e".into());
-            args.push(self.file.clone().into());
-            args.extend(self.pacfiles.iter().map(|p| p.into()));
+        if let Some(pacfile) = self.pacfiles.get(selected) {
+            if let Ok(target) = fs::read_link(pacfile) {
+                links.push_str(&format!(" ({} -> {})", self.kind, escape_path(&target)));
+            }
         }
 
-        let mut command = Command::new(bin);
-        command.args(&args);
+        links
+    }
 
-        let exit = match command.spawn() {
-            Err(err) => {
-                return Err(Error::CommandFailed(
-                    bin.to_string(),
-                    args.iter()
-                        .map(|s| s.to_string_lossy().to_string())
-                        .collect(),
-                    err,
-                ));
+    pub fn newest(&self) -> usize {
+        let mut newest = self.pacfiles.len().saturating_sub(1);
+        let mut time = None;
+
+        for (n, file) in self.pacfiles.iter().enumerate() {
+            let modified = mtime(file).ok();
+            if modified > time {
+                newest = n;
+                time = modified;
             }
-            Ok(o) => o,
         }
-        .wait()?;
 
-        if !exit.success() {
-            let e = config.color.error;
-            let err = Error::CommandNonZero(
-                bin.to_string(),
-                args.iter()
-                    .map(|s| s.to_string_lossy().to_string())
-                    .collect(),
-                exit.code(),
+        newest
+    }
+
+    pub fn age(&self) -> Option<Duration> {
+        mtime(&self.pacfiles[self.newest()]).ok()?.elapsed().ok()
+    }
+
+    fn choose(&self, config: &Config, selected: usize) -> Result<usize> {
+        let maxnum = self.pacfiles.len().to_string().len();
+        let maxfile = self
+            .pacfiles
+            .iter()
+            .map(|p| escape_path(p).chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (n, file) in self.pacfiles.iter().enumerate() {
+            println!(
+                "{} {:num$}  {:file$}  {}",
+                if n == selected { "*" } else { " " },
+                n + 1,
+                escape_path(file),
+                format_time(mtime(file).ok()),
+                num = maxnum,
+                file = maxfile,
             );
-            eprintln!("{} {}", e.paint("error:"), err);
         }
 
-        Ok(())
+        let prompt = format!("Pacfile to use (1-{}): ", self.pacfiles.len());
+        let input = readline(config, &prompt)?;
+
+        match input.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= self.pacfiles.len() => Ok(n - 1),
+            _ => Ok(selected),
+        }
+    }
+
+    pub fn view(&self, config: &Config, pacfile: &Path) -> Result<()> {
+        for file in [&*self.file, pacfile].iter() {
+            if let Ok(target) = fs::read_link(file) {
+                println!(
+                    "{} '{}' is a symlink to '{}'",
+                    config.color.info.paint("note:"),
+                    escape_path(file),
+                    escape_path(&target)
+                );
+            }
+        }
+
+        let diffprog = match config.diffprog {
+            Some(ref diffprog) => diffprog.as_str(),
+            None => match ["vim -d", "git diff --no-index", "diff"]
+                .iter()
+                .find(|d| which(d.split_whitespace().next().unwrap()).is_ok())
+            {
+                Some(diffprog) => diffprog,
+                None => {
+                    return Err(Error::Io(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "no suitable diff program found",
+                    )))
+                }
+            },
+        };
+
+        let live = self.live_file();
+
+        if !binary::is_binary(live) && !binary::is_binary(pacfile) {
+            // sudoedit refuses /dev/null, which stands in for a deleted original
+            return run_editor(config, diffprog, &[live, pacfile], !self.missing);
+        }
+
+        let names = ("current", &*self.kind.to_string());
+        let (dir, live, pacfile) = binary::render(config, names, live, pacfile)?;
+        let res = run_editor(config, diffprog, &[&live, &pacfile], false);
+        let _ = fs::remove_dir_all(&dir);
+        res
     }
 
-    fn view(&self, config: &Config) -> Result<()> {
-        if let Some(ref diffprog) = config.diffprog {
-            self.diffprog(config, diffprog)
+    fn merge(&self, config: &Config, errors: &mut Vec<Error>, selected: usize) -> Option<bool> {
+        let mergeprog = config.mergeprog.as_deref().unwrap_or("vim -d");
+
+        let files = [&*self.file, &self.pacfiles[selected]];
+        let before = fs::read(&self.file).ok();
+
+        if let Err(err) = run_editor(config, mergeprog, &files, true) {
+            report(config, errors, err);
+            return Some(false);
+        }
+
+        // quitting the merge program without saving must not lose the pacfile
+        if fs::read(&self.file).ok() == before {
+            let prompt = "The file was not changed, remove the pacfile anyway? [y/N] ";
+            match readline(config, prompt) {
+                Ok(input) if input.trim().to_lowercase().starts_with('y') => (),
+                Ok(_) => return None,
+                Err(err) => {
+                    report(config, errors, err);
+                    return None;
+                }
+            }
+        }
+
+        Some(self.remove(config, errors))
+    }
+
+    fn remove(&self, config: &Config, errors: &mut Vec<Error>) -> bool {
+        let mut ok = true;
+
+        for file in &self.pacfiles {
+            if let Err(err) = fs::remove_file(file) {
+                report(config, errors, Error::File("remove", file.clone(), err));
+                ok = false;
+            }
+        }
+
+        ok
+    }
+
+    fn symlink_mode(&self, config: &Config, selected: usize) -> Result<Option<Symlink>> {
+        let target = match fs::read_link(&self.file) {
+            Ok(target) => target,
+            Err(_) => return Ok(Some(Symlink::replace)),
+        };
+
+        if self
+            .pacfiles
+            .get(selected)
+            .and_then(|p| fs::read_link(p).ok())
+            .is_some()
+        {
+            return Ok(Some(Symlink::replace));
+        }
+        if let Some(mode) = config.symlinks {
+            return Ok(Some(mode));
+        }
+        if config.action.is_some() {
+            return Ok(Some(Symlink::through));
+        }
+
+        println!(
+            "{} '{}' is a symlink to '{}'",
+            config.color.info.paint("note:"),
+            escape_path(&self.file),
+            escape_path(&target)
+        );
+
+        let input = readline(
+            config,
+            "[W]rite through the link [R]eplace the link [C]ancel: ",
+        )?
+        .to_lowercase();
+
+        if input.starts_with('w') {
+            Ok(Some(Symlink::through))
+        } else if input.starts_with('r') {
+            Ok(Some(Symlink::replace))
         } else {
-            for diffprog in &["vim -d", "git diff --no-index", "diff"] {
-                let bin = diffprog.split_whitespace().next().unwrap();
-                if which(bin).is_ok() {
-                    self.diffprog(config, diffprog)?;
-                    return Ok(());
+            Ok(None)
+        }
+    }
+
+    fn overwrite(
+        &self,
+        config: &Config,
+        errors: &mut Vec<Error>,
+        mode: Symlink,
+        selected: usize,
+    ) -> bool {
+        let mut ok = true;
+
+        let dest = match mode {
+            Symlink::replace => self.file.clone(),
+            Symlink::through => match resolve_in_root(&self.root, &self.file) {
+                Ok(dest) => dest,
+                Err(err) => {
+                    report(
+                        config,
+                        errors,
+                        Error::File("resolve", self.file.clone(), err),
+                    );
+                    return false;
                 }
+            },
+        };
+
+        if let Some(file) = self.pacfiles.get(selected) {
+            if let Err(err) = install(file, &dest) {
+                report(config, errors, err);
+                return false;
             }
+        }
 
-            eprintln!("no suitable diff program found");
-            Ok(())
+        for (_, file) in self
+            .pacfiles
+            .iter()
+            .enumerate()
+            .filter(|(n, _)| *n != selected)
+        {
+            if let Err(err) = fs::remove_file(file) {
+                report(config, errors, Error::File("remove", file.clone(), err));
+                ok = false;
+            }
         }
+
+        ok
     }
 
-    fn manage(&self, config: &Config, curr: usize, total: usize) -> Result<bool> {
-        let e = config.color.error;
+    // the action returns None when it was abandoned and the pacfile left alone
+    fn action<F: FnOnce(&mut Vec<Error>) -> Option<bool>>(
+        &self,
+        config: &Config,
+        errors: &mut Vec<Error>,
+        outcome: Outcome,
+        action: F,
+    ) -> Outcome {
+        let name = outcome.to_string();
+
+        if !hooks::pre(config, self, &name, errors) {
+            return Outcome::Skipped;
+        }
+
+        let ok = match action(errors) {
+            Some(ok) => ok,
+            None => return Outcome::Skipped,
+        };
+
+        hooks::post(config, self, &name, errors);
+
+        if ok {
+            outcome
+        } else {
+            Outcome::Failed
+        }
+    }
+
+    pub fn manage(
+        &self,
+        config: &Config,
+        repo: Option<&Repo>,
+        errors: &mut Vec<Error>,
+        curr: usize,
+        total: usize,
+        preset: Option<&str>,
+    ) -> Result<Outcome> {
         let b = config.color.bold;
         let maxnum = total.to_string().len();
+        let mut selected = self.newest();
+        let preset = preset
+            .map(|p| p.to_string())
+            .or_else(|| config.action.as_ref().map(|a| a.to_string()));
 
         loop {
             let input;
 
-            if let Some(ref action) = config.action {
-                input = action.to_string();
+            if let Some(ref preset) = preset {
+                input = preset.clone();
             } else {
                 println!(
                     "\n{} [{:0num$}/{:num$}] {}",
                     config.color.info.paint("==>"),
                     curr,
                     total,
-                    b.paint(self.format_pacfiles()),
+                    b.paint(self.format_file(selected)),
                     num = maxnum,
                 );
-                let line = readline(config, "[V]iew [S]kip [R]emove [O]verwrite [Q]uit: ")?;
-                input = line.to_lowercase();
-            }
 
-            if input.starts_with('v') {
-                self.view(config)?;
-            } else if input.starts_with('s') {
-                break;
-            } else if input.starts_with('r') {
-                for file in &self.pacfiles {
-                    if let Err(err) = fs::remove_file(file) {
-                        eprintln!(
-                            "{} failed to remove '{}': {}",
-                            e.paint("error:",),
-                            file.display(),
-                            err
-                        );
-                    }
+                if self.pacfiles.len() > 1 {
+                    println!(
+                        "{} using {} from {}",
+                        config.color.info.paint("::"),
+                        escape_path(&self.pacfiles[selected]),
+                        format_time(mtime(&self.pacfiles[selected]).ok()),
+                    );
                 }
-                break;
-            } else if input.starts_with('o') {
-                let mut iter = self.pacfiles.iter();
-
-                if let Some(file) = iter.next_back() {
-                    if let Err(err) = fs::rename(file, &self.file) {
-                        eprintln!(
-                            "{} failed to move '{}' to '{}': {}",
-                            e.paint("error:"),
-                            file.display(),
-                            self.file.display(),
-                            err
-                        );
-                    }
+
+                let mut choices = vec![("[V]iew", "view the differences with the diff program")];
+                if repo.is_some() {
+                    choices.push((
+                        "[G]itlog",
+                        "show the history of the file in the /etc repository",
+                    ));
+                }
+                if self.pacfiles.len() > 1 {
+                    choices.push(("[C]hoose", "choose which pacfile to use"));
+                }
+                choices.push(("[S]kip", "leave the pacfile for later, the default"));
+                if self.missing {
+                    choices.push(("[I]nstall", "recreate the deleted file from the pacfile"));
+                    choices.push(("[D]iscard", "remove the pacfile"));
+                    choices.push((
+                        "[K]eep deleted",
+                        "remove the pacfile and keep the file deleted",
+                    ));
+                } else {
+                    choices.push(("[R]emove", "remove the pacfile"));
+                    choices.push(("[O]verwrite", "replace the file with the pacfile"));
+                    choices.push((
+                        "[M]erge",
+                        "merge with the merge program, then remove the pacfile",
+                    ));
+                }
+                if curr > 1 {
+                    choices.push(("[B]ack", "go back to the previous file"));
+                }
+                if curr < total {
+                    choices.push(("[N]ext", "go to the next file without deciding"));
                 }
+                choices.push(("[Q]uit", "stop here and keep what was done so far"));
 
-                for file in iter {
-                    if let Err(err) = fs::remove_file(file) {
-                        eprintln!(
-                            "{} failed to remove '{}': {}",
-                            e.paint("error:"),
-                            file.display(),
-                            err
-                        );
-                    }
+                let line = read_choice(config, &choices, "[S]kip")?;
+                input = line.trim().to_lowercase();
+
+                if input == "?" {
+                    print_choices_help(&choices, total);
+                    continue;
                 }
+            }
+
+            if self.missing && config.action.is_some() && input.starts_with('o') {
+                println!(
+                    "{} not recreating deleted file '{}'",
+                    config.color.info.paint("note:"),
+                    escape_path(&self.file)
+                );
                 break;
+            }
+
+            if input.is_empty() || input.starts_with('s') {
+                break;
+            } else if input.starts_with('v') {
+                if let Err(err) = self.view(config, &self.pacfiles[selected]) {
+                    report(config, errors, err);
+                }
+            } else if input.starts_with('c') && self.pacfiles.len() > 1 {
+                selected = self.choose(config, selected)?;
+            } else if let (true, Some(repo)) = (input.starts_with('g'), repo) {
+                if let Err(err) = repo.log(&self.file) {
+                    report(config, errors, err);
+                }
+            } else if input.starts_with('r') || (self.missing && input.starts_with('d')) {
+                let remove = |errors: &mut Vec<Error>| Some(self.remove(config, errors));
+                return Ok(self.action(config, errors, Outcome::Removed, remove));
+            } else if self.missing && input.starts_with('k') {
+                let keep_deleted = |errors: &mut Vec<Error>| {
+                    if let Err(err) = deleted::record(&self.root, &self.file) {
+                        report(config, errors, err);
+                        return Some(false);
+                    }
+                    Some(self.remove(config, errors))
+                };
+                return Ok(self.action(config, errors, Outcome::Removed, keep_deleted));
+            } else if input.starts_with('o') || (self.missing && input.starts_with('i')) {
+                let mode = match self.symlink_mode(config, selected)? {
+                    Some(mode) => mode,
+                    None => continue,
+                };
+                let overwrite =
+                    |errors: &mut Vec<Error>| Some(self.overwrite(config, errors, mode, selected));
+                return Ok(self.action(config, errors, Outcome::Overwritten, overwrite));
+            } else if !self.missing && input.starts_with('m') {
+                let merge = |errors: &mut Vec<Error>| self.merge(config, errors, selected);
+                return Ok(self.action(config, errors, Outcome::Merged, merge));
             } else if input.starts_with('q') {
-                return Ok(true);
+                return Ok(Outcome::Quit);
+            } else if input.starts_with('b') && curr > 1 {
+                return Ok(Outcome::Back);
+            } else if input.starts_with('n') && curr < total {
+                return Ok(Outcome::Next);
+            } else if let Ok(n) = input.parse::<usize>() {
+                if n >= 1 && n <= total {
+                    return Ok(Outcome::Jump(n));
+                }
+                eprintln!(
+                    "{} there is no file {}, expected 1-{}",
+                    config.color.error.paint("error:"),
+                    n,
+                    total
+                );
             } else {
+                eprintln!(
+                    "{} unknown choice '{}', enter a letter from the prompt or a file number",
+                    config.color.error.paint("error:"),
+                    input
+                );
+            }
+
+            // a preset that only looks at the file would otherwise repeat forever
+            if preset.is_some() {
                 break;
             }
         }
 
-        Ok(false)
+        Ok(Outcome::Skipped)
     }
 }
 
-fn print_backups(config: &Config, backups: &[Backup]) {
-    let mut maxnum = "".len();
-    let mut maxpkg = "Package".len();
-    let mut maxfile = "File".len();
-    let b = config.color.bold;
-
-    for (n, backup) in backups.iter().enumerate() {
-        for (m, _) in backup.pacfiles.iter().enumerate() {
-            maxnum = m + n + 1;
-            maxpkg = maxpkg.max(backup.package.len());
+// follows a symlink as it would resolve inside the root, so the absolute links of a
+// container don't lead to files on the host
+fn resolve_in_root(root: &Path, file: &Path) -> io::Result<PathBuf> {
+    let mut dest = file.to_path_buf();
+    let mut links = 0;
+
+    while let Ok(target) = fs::read_link(&dest) {
+        links += 1;
+        if links > 40 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many levels of symbolic links",
+            ));
         }
 
-        let files = backup.format_pacfiles();
-        maxfile = maxfile.max(files.len());
+        dest = match target.strip_prefix("/") {
+            Ok(target) => root.join(target),
+            Err(_) => dest.parent().unwrap_or(root).join(target),
+        };
     }
 
-    let maxnum = maxnum.to_string().len();
+    let name = dest.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "symlink does not name a file")
+    })?;
+    let parent = fs::canonicalize(dest.parent().unwrap_or(root))?;
 
-    let header = format!(
-        "{:num$}  {:pkg$}  {:file$}  {}",
-        "",
-        "Package",
-        "File",
-        "Modified",
-        num = maxnum,
-        pkg = maxpkg,
-        file = maxfile
-    );
+    if !parent.starts_with(fs::canonicalize(root)?) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "symlink points outside of the root",
+        ));
+    }
 
-    println!("{}", b.paint(header));
+    Ok(parent.join(name))
+}
 
-    for (n, backup) in backups.iter().enumerate() {
-        let mut time = None;
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<(String, Style)>>,
+    pub widths: Vec<usize>,
+}
 
-        for file in &backup.pacfiles.iter().last() {
-            let metadata = file.metadata();
-            let metadata = match metadata {
-                Ok(o) => o,
-                Err(err) => {
-                    println!("failed to stat '{}': {}", file.display(), err);
-                    continue;
-                }
-            };
-            let modified = metadata.modified();
-            let modified = match modified {
-                Ok(o) => o,
-                Err(err) => {
-                    println!("{}", err);
-                    continue;
+// the numbered pacfile table, shared by the selection menu and the TUI list
+pub fn table<'a, I>(config: &Config, backups: I, errors: &mut Vec<Error>) -> Table
+where
+    I: IntoIterator<Item = &'a Backup>,
+{
+    let multiroot = config.machines || config.root.len() > 1;
+    let columns = if config.columns.is_empty() {
+        let mut columns = vec!["package", "file", "modified"];
+        if multiroot {
+            columns.insert(0, "root");
+        }
+        columns
+    } else {
+        config.columns.iter().map(|c| c.as_str()).collect()
+    };
+
+    let days = config.warning_age.unwrap_or(OLD_AGE_DAYS);
+    let old = Duration::from_secs(days.saturating_mul(24 * 60 * 60));
+    let mut rows = Vec::new();
+
+    for (n, backup) in backups.into_iter().enumerate() {
+        let mut row = vec![((n + 1).to_string(), Style::default())];
+        let kind = match backup.kind {
+            Kind::Pacnew => config.color.pacnew,
+            Kind::Pacsave => config.color.pacsave,
+        };
+
+        for column in &columns {
+            let cell = match *column {
+                "root" => (escape_path(&backup.root), Style::default()),
+                "package" => (backup.package.clone(), Style::default()),
+                "file" => (backup.format_file(backup.newest()), kind),
+                "kind" => (backup.kind.to_string(), kind),
+                _ => {
+                    let file = &backup.pacfiles[backup.newest()];
+                    let time = match mtime(file) {
+                        Ok(time) => Some(time),
+                        Err(err) => {
+                            errors.push(Error::File("stat", file.clone(), err));
+                            None
+                        }
+                    };
+                    let age = time.and_then(|t| t.elapsed().ok()).unwrap_or_default();
+                    let style = if age >= old {
+                        config.color.age
+                    } else {
+                        Style::default()
+                    };
+                    (format_time(time), style)
                 }
             };
+            row.push(cell);
+        }
+
+        rows.push(row);
+    }
 
-            time = Some(DateTime::<Utc>::from(modified));
+    let mut header = vec![String::new()];
+    for column in &columns {
+        let mut name = column.to_string();
+        name[..1].make_ascii_uppercase();
+        header.push(name);
+    }
+
+    let mut widths = header.iter().map(|h| h.len()).collect::<Vec<_>>();
+    for row in &rows {
+        for (width, (cell, _)) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
+    }
 
-        let time = if let Some(time) = time {
-            time.format("%c").to_string()
-        } else {
-            "Unknown".to_string()
-        };
+    Table {
+        header,
+        rows,
+        widths,
+    }
+}
 
-        println!(
-            "{:0num$}  {:pkg$}  {:file$}  {}",
-            n + 1,
-            backup.package,
-            backup.format_pacfiles(),
-            time,
-            num = maxnum,
-            pkg = maxpkg,
-            file = maxfile
-        );
+fn print_backups(config: &Config, backups: &[Backup], errors: &mut Vec<Error>) {
+    let b = config.color.bold;
+    let table = table(config, backups, errors);
+    let widths = &table.widths;
+
+    let last = widths.len() - 1;
+    let format_row = |row: &[(String, Style)], number: bool| {
+        let mut line = String::new();
+        for (n, ((cell, style), width)) in row.iter().zip(widths).enumerate() {
+            let pad = width - cell.chars().count();
+            if n > 0 {
+                line.push_str("  ");
+            }
+            if n == 0 && number {
+                line.push_str(&"0".repeat(pad));
+                line.push_str(cell);
+            } else {
+                line.push_str(&style.paint(cell.as_str()).to_string());
+                if n != last {
+                    line.push_str(&" ".repeat(pad));
+                }
+            }
+        }
+        line
+    };
+
+    let header = table
+        .header
+        .iter()
+        .map(|h| (h.clone(), b))
+        .collect::<Vec<_>>();
+    println!("{}", format_row(&header, false));
+
+    for row in &table.rows {
+        println!("{}", format_row(row, true));
     }
 }
 
-fn filter_backups(backups: Vec<Backup>, input: &str) -> Vec<Backup> {
-    if input.trim().is_empty() {
-        return backups;
+// a "-" target is replaced by the targets listed on stdin, one per line
+pub fn read_targets(config: &mut Config) -> Result<()> {
+    if !config.targets.iter().any(|t| t == "-") {
+        return Ok(());
     }
 
-    let mut whitelist = vec![false; backups.len()];
-    let tokens = input.split_whitespace();
+    let mut targets = Vec::new();
 
-    for token in tokens {
-        let len = token.len();
-        let token = token.trim_start_matches('^');
-        let invert = token.len() != len;
+    for target in config.targets.drain(..) {
+        if target != "-" {
+            targets.push(target);
+            continue;
+        }
 
-        if token.starts_with('a') {
-            for b in &mut whitelist {
-                *b = true;
-            }
-        } else if token.starts_with('n') {
-            for b in &mut whitelist {
-                *b = false;
+        // paths are read as bytes, they don't have to be valid UTF-8
+        for line in io::stdin().lock().split(b'\n') {
+            let line = line?;
+            let start = line.iter().position(|c| !c.is_ascii_whitespace());
+            let end = line.iter().rposition(|c| !c.is_ascii_whitespace());
+            if let (Some(start), Some(end)) = (start, end) {
+                targets.push(OsStr::from_bytes(&line[start..=end]).to_os_string());
             }
         }
+    }
 
-        let mut range = token.splitn(2, '-');
-        let min = range.next().unwrap();
+    config.targets = targets;
 
-        let min = match min.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => continue,
-        };
+    let prompts = config.action.is_none()
+        && !config.output
+        && !config.check
+        && !config.watch
+        && config.prometheus.is_none();
+    if !prompts {
+        return Ok(());
+    }
 
-        let max = match range.next() {
-            Some(c) => c.parse::<usize>().unwrap_or(min),
-            None => min,
-        };
+    // stdin is used up, so take prompts from the terminal instead
+    let tty = fs::File::open("/dev/tty")
+        .map_err(|e| Error::File("open terminal for prompts", "/dev/tty".into(), e))?;
+
+    // SAFETY: both descriptors are open for the duration of the call, dup2 only
+    // replaces stdin and leaves tty to be closed when it is dropped
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } == -1 {
+        let err = io::Error::last_os_error();
+        return Err(Error::File(
+            "open terminal for prompts",
+            "/dev/tty".into(),
+            err,
+        ));
+    }
 
-        let range = if min < max { min..=max } else { max..=min };
+    Ok(())
+}
 
-        for (n, b) in whitelist.iter_mut().enumerate() {
-            if range.contains(&(n + 1)) != invert {
-                *b = true
-            }
+fn backup_owner(alpm: &Alpm, file: &[u8]) -> Option<String> {
+    alpm.localdb()
+        .pkgs()
+        .iter()
+        .find(|pkg| pkg.backup().iter().any(|b| b.name().as_bytes() == file))
+        .map(|pkg| pkg.name().to_string())
+}
+
+fn path_target(alpm: &Alpm, target: &OsStr) -> std::result::Result<(String, PathBuf), Error> {
+    let root = Path::new(alpm.root());
+    let path = Path::new(target);
+
+    // absolute paths are on the host, relative ones are relative to the root like alpm hook targets
+    let relative = if path.is_absolute() {
+        path.strip_prefix(root).unwrap_or(path)
+    } else {
+        path
+    };
+    let relative = relative.as_os_str().as_bytes();
+
+    let mut candidates = vec![relative];
+    let name_start = relative
+        .iter()
+        .rposition(|&c| c == b'/')
+        .map_or(0, |i| i + 1);
+    for kind in [&b".pacnew"[..], &b".pacsave"[..]].iter() {
+        if let Some(i) = relative[name_start..]
+            .windows(kind.len())
+            .position(|w| w == *kind)
+        {
+            candidates.push(&relative[..name_start + i]);
         }
     }
 
-    backups
-        .into_iter()
-        .enumerate()
-        .filter(|(n, _)| whitelist[*n])
-        .map(|(_, b)| b)
-        .collect()
+    for file in candidates {
+        if let Some(package) = backup_owner(alpm, file) {
+            return Ok((package, root.join(OsStr::from_bytes(file))));
+        }
+    }
+
+    let owner = alpm
+        .localdb()
+        .pkgs()
+        .iter()
+        .find(|pkg| pkg.files().contains(relative).ok().flatten().is_some())
+        .map(|pkg| pkg.name().to_string());
+
+    match owner {
+        Some(owner) => Err(Error::Target(format!(
+            "{} is not a backup file of {}",
+            escape_path(path),
+            owner
+        ))),
+        None => Err(Error::Target(format!(
+            "no package owns {}",
+            escape_path(path)
+        ))),
+    }
 }
 
-fn get_backups(config: &Config, alpm: &Alpm) -> Result<Vec<Backup>> {
+fn backup_files(config: &Config, alpm: &Alpm, errors: &mut Vec<Error>) -> Vec<(String, PathBuf)> {
     let root = Path::new(alpm.root());
-    let mut backups = Vec::new();
+    let mut files = Vec::new();
     let mut pkgs = Vec::new();
-    let e = config.color.error;
-    let b = config.color.bold;
 
     if config.targets.is_empty() {
         pkgs.extend(alpm.localdb().pkgs());
     } else {
         for target in &config.targets {
-            match alpm.localdb().pkg(target.clone()) {
-                Ok(p) => pkgs.push(p),
-                Err(_) => eprintln!(
-                    "{} target not found: {}",
-                    e.paint("error:"),
-                    b.paint(target)
-                ),
+            if target.as_bytes().contains(&b'/') {
+                // an absolute path only belongs to the root it is under
+                let path = Path::new(target);
+                if path.is_absolute() && !path.starts_with(root) {
+                    continue;
+                }
+
+                match path_target(alpm, target) {
+                    Ok(file) => files.push(file),
+                    Err(err) => report(config, errors, err),
+                }
+                continue;
+            }
+
+            match target.to_str().map(|t| alpm.localdb().pkg(t)) {
+                Some(Ok(p)) => pkgs.push(p),
+                _ => {
+                    let target = escape_path(Path::new(target));
+                    let err = Error::Target(format!("target not found: {}", target));
+                    report(config, errors, err);
+                }
             }
         }
     }
 
     for pkg in pkgs {
         for backup in pkg.backup() {
-            let path = root.join(&backup.name());
-            let (mut pacnew, mut pacsave) = find_backups_for_file(config, &path)?;
-            pacnew.sort_by(|a, b| natord::compare(&a.to_string_lossy(), &b.to_string_lossy()));
-            pacsave.sort_by(|a, b| natord::compare(&a.to_string_lossy(), &b.to_string_lossy()));
-
-            if !pacnew.is_empty() {
-                let backup = Backup {
-                    package: pkg.name().into(),
-                    file: path.clone(),
-                    pacfiles: pacnew,
-                    kind: Kind::Pacnew,
-                };
+            files.push((pkg.name().to_string(), root.join(backup.name())));
+        }
+    }
 
-                backups.push(backup);
-            }
+    let mut seen = Vec::new();
+    files.retain(|(_, file)| {
+        if seen.contains(file) {
+            false
+        } else {
+            seen.push(file.clone());
+            true
+        }
+    });
 
-            if !pacsave.is_empty() {
-                let backup = Backup {
-                    package: pkg.name().into(),
-                    file: path,
-                    pacfiles: pacsave,
-                    kind: Kind::Pacsave,
-                };
+    files
+}
 
-                backups.push(backup);
-            }
+pub fn backups_for_file(
+    config: &Config,
+    errors: &mut Vec<Error>,
+    root: &Path,
+    package: &str,
+    path: &Path,
+) -> Vec<Backup> {
+    let mut backups = Vec::new();
+    let missing = path.symlink_metadata().is_err();
+    let (mut pacnew, mut pacsave) = find_backups_for_file(config, errors, path);
+    pacnew.sort_by(compare_paths);
+    pacsave.sort_by(compare_paths);
+
+    if !pacnew.is_empty() {
+        let backup = Backup {
+            root: root.into(),
+            package: package.into(),
+            file: path.into(),
+            pacfiles: pacnew,
+            kind: Kind::Pacnew,
+            missing,
+        };
+
+        backups.push(backup);
+    }
+
+    if !pacsave.is_empty() {
+        let backup = Backup {
+            root: root.into(),
+            package: package.into(),
+            file: path.into(),
+            pacfiles: pacsave,
+            kind: Kind::Pacsave,
+            missing,
+        };
+
+        backups.push(backup);
+    }
+
+    backups
+}
+
+fn wanted_package(config: &Config, alpm: &Alpm, package: &str) -> bool {
+    let pkg = match alpm.localdb().pkg(package) {
+        Ok(pkg) => pkg,
+        Err(_) => return false,
+    };
+
+    if config.explicit && pkg.reason() != PackageReason::Explicit {
+        return false;
+    }
+    if config.deps && pkg.reason() != PackageReason::Depend {
+        return false;
+    }
+    if !config.groups.is_empty()
+        && !pkg
+            .groups()
+            .iter()
+            .any(|g| config.groups.iter().any(|c| c == g))
+    {
+        return false;
+    }
+
+    if !config.repos.is_empty() {
+        // the first repo to carry the package is the one pacman would install it from
+        let repo = alpm
+            .syncdbs()
+            .iter()
+            .find(|db| db.pkg(package).is_ok())
+            .map(|db| db.name().to_string());
+
+        match repo {
+            Some(repo) if config.repos.contains(&repo) => (),
+            _ => return false,
         }
     }
 
-    Ok(backups)
+    true
+}
+
+// the backup files of the packages selected by the targets and package filters
+pub fn package_files(
+    config: &Config,
+    alpm: &Alpm,
+    errors: &mut Vec<Error>,
+) -> Vec<(String, PathBuf)> {
+    let mut files = backup_files(config, alpm, errors);
+
+    let filter_packages =
+        !config.repos.is_empty() || !config.groups.is_empty() || config.explicit || config.deps;
+    if filter_packages {
+        files.retain(|(package, _)| wanted_package(config, alpm, package));
+    }
+
+    files
+}
+
+pub fn wanted_backup(config: &Config, deleted: &[PathBuf], backup: &Backup) -> bool {
+    (!backup.missing || !deleted.contains(&backup.file))
+        && (config.kinds.is_empty() || config.kinds.contains(&backup.kind.to_string()))
+        && (config.paths.is_empty() || config.paths.iter().any(|p| path_matches(p, backup)))
+        && !config.exclude_paths.iter().any(|p| path_matches(p, backup))
+        && !config
+            .exclude_packages
+            .iter()
+            .any(|p| p.matches(&backup.package))
+}
+
+pub fn get_backups(config: &Config, alpm: &Alpm, errors: &mut Vec<Error>) -> Vec<Backup> {
+    let mut backups = Vec::new();
+    let root = Path::new(alpm.root());
+    let deleted = deleted::load(root);
+
+    for (package, path) in package_files(config, alpm, errors) {
+        backups.extend(backups_for_file(config, errors, root, &package, &path));
+    }
+
+    backups.retain(|backup| wanted_backup(config, &deleted, backup));
+    backups
 }
 
-fn find_backups_for_file(config: &Config, file: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+fn compare_paths(a: &PathBuf, b: &PathBuf) -> Ordering {
+    natord::compare(&a.to_string_lossy(), &b.to_string_lossy()).then_with(|| a.cmp(b))
+}
+
+pub fn pacfile_kind(file: &Path, pacfile: &OsStr) -> Option<Kind> {
+    let filename = file.file_name()?.as_bytes();
+    let suffix = pacfile.as_bytes().strip_prefix(filename)?;
+
+    if suffix.starts_with(b".pacnew") {
+        Some(Kind::Pacnew)
+    } else if suffix.starts_with(b".pacsave") {
+        Some(Kind::Pacsave)
+    } else {
+        None
+    }
+}
+
+fn find_backups_for_file(
+    config: &Config,
+    errors: &mut Vec<Error>,
+    file: &Path,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
     let mut newfiles = Vec::new();
     let mut savefiles = Vec::new();
-    let e = config.color.error;
 
     let parent = match file.parent() {
         Some(o) => o,
-        None => return Ok((newfiles, savefiles)),
+        None => return (newfiles, savefiles),
     };
 
     let read = fs::read_dir(parent);
     let read = match read {
         Ok(read) => read,
         Err(err) => {
-            if config.verbose {
-                eprintln!("{} {}: {}", e.paint("error:"), parent.display(), err);
+            if config.verbose || err.kind() != io::ErrorKind::NotFound {
+                errors.push(Error::File("read directory", parent.into(), err));
             }
-            return Ok((newfiles, savefiles));
+            return (newfiles, savefiles);
         }
     };
 
-    let filename = match file.file_name() {
-        Some(o) => o,
-        None => return Ok((newfiles, savefiles)),
-    }
-    .to_string_lossy();
-
-    let pacsave = format!("{}.pacnew", filename);
-    let pacnew = format!("{}.pacsave", filename);
-
     for entry in read {
         let entry = match entry {
             Ok(entry) => entry,
             Err(err) => {
-                if config.verbose {
-                    eprintln!("{} {}: {}", e.paint("error:"), parent.display(), err);
-                }
+                errors.push(Error::File("read directory", parent.into(), err));
                 continue;
             }
         };
 
-        let pacfile = entry.file_name();
-        let pacfile = pacfile.to_string_lossy();
-
-        if pacfile.starts_with(&pacsave) {
-            newfiles.push(entry.path());
-        } else if pacfile.starts_with(&pacnew) {
-            savefiles.push(entry.path());
+        match pacfile_kind(file, &entry.file_name()) {
+            Some(Kind::Pacnew) => newfiles.push(entry.path()),
+            Some(Kind::Pacsave) => savefiles.push(entry.path()),
+            None => (),
         }
     }
 
-    Ok((newfiles, savefiles))
+    (newfiles, savefiles)
 }
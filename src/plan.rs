@@ -0,0 +1,300 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::pacdiff::{private_temp_dir, readline, report, run_editor, Backup};
+use crate::util::escape_path;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const HELP: &str = "
+# Edit the action at the start of each line, then save and quit to run the plan.
+# Each line is: action package kind file
+# Lines are run from top to bottom. Removing a line skips its file.
+#
+# Actions:
+#   s, skip       leave the pacfile alone
+#   r, remove     remove the pacfile
+#   o, overwrite  replace the file with the pacfile
+#   m, merge      merge with the merge program, then remove the pacfile
+#
+# Actions for files whose original was deleted:
+#   i, install    recreate the file from the pacfile
+#   d, discard    remove the pacfile
+#   k, keep       remove the pacfile and keep the file deleted
+#
+# Removing every line aborts the plan.
+";
+
+const ACTIONS: &[(&str, &str)] = &[
+    ("s", "skip"),
+    ("r", "remove"),
+    ("o", "overwrite"),
+    ("m", "merge"),
+    ("i", "install"),
+    ("d", "discard"),
+    ("k", "keep"),
+];
+
+fn action(word: &str, missing: bool) -> Option<&'static str> {
+    let action = ACTIONS
+        .iter()
+        .find(|(short, long)| word == *short || word == *long)
+        .map(|(_, long)| *long)?;
+
+    let allowed = match action {
+        "skip" => true,
+        "remove" | "overwrite" | "merge" => !missing,
+        _ => missing,
+    };
+
+    if allowed {
+        Some(action)
+    } else {
+        None
+    }
+}
+
+fn write_plan(backups: &[&Backup]) -> String {
+    let mut plan = String::new();
+
+    for backup in backups {
+        plan.push_str(&format!(
+            "skip {} {} {}\n",
+            backup.package,
+            backup.kind,
+            escape_path(&backup.file)
+        ));
+    }
+
+    plan.push_str(HELP);
+    plan
+}
+
+// splits off the first word, however much whitespace follows it
+fn next_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    (&s[..end], s[end..].trim_start())
+}
+
+fn parse_plan<'a>(
+    backups: &[&'a Backup],
+    plan: &str,
+) -> std::result::Result<Vec<(&'a Backup, &'static str)>, Vec<String>> {
+    let mut steps: Vec<(&Backup, &str)> = Vec::new();
+    let mut problems = Vec::new();
+
+    for (n, line) in plan.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (word, rest) = next_word(line);
+        let (package, rest) = next_word(rest);
+        let (kind, file) = next_word(rest);
+
+        let backup = match backups.iter().find(|b| {
+            b.package == package && b.kind.to_string() == kind && escape_path(&b.file) == file
+        }) {
+            Some(backup) => backup,
+            None => {
+                problems.push(format!(
+                    "line {}: no pacfile for '{} {} {}'",
+                    n + 1,
+                    package,
+                    kind,
+                    file
+                ));
+                continue;
+            }
+        };
+
+        if steps.iter().any(|(b, _)| std::ptr::eq(*b, *backup)) {
+            problems.push(format!(
+                "line {}: '{} {}' is listed twice",
+                n + 1,
+                kind,
+                file
+            ));
+            continue;
+        }
+
+        match action(word, backup.missing) {
+            Some(action) => steps.push((backup, action)),
+            None => problems.push(format!(
+                "line {}: invalid action '{}' for '{}'",
+                n + 1,
+                word,
+                file
+            )),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(steps)
+    } else {
+        Err(problems)
+    }
+}
+
+fn editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".into())
+}
+
+fn edit_plan<'a>(
+    config: &Config,
+    backups: &[&'a Backup],
+    errors: &mut Vec<Error>,
+    file: &Path,
+) -> Result<Vec<(&'a Backup, &'static str)>> {
+    let e = config.color.error;
+    let editor = editor();
+
+    fs::write(file, write_plan(backups)).map_err(|e| Error::File("write", file.into(), e))?;
+
+    loop {
+        if let Err(err) = run_editor(config, &editor, &[file], true) {
+            report(config, errors, err);
+            return Ok(Vec::new());
+        }
+
+        let plan = fs::read_to_string(file).map_err(|e| Error::File("read", file.into(), e))?;
+
+        match parse_plan(backups, &plan) {
+            Ok(steps) => return Ok(steps),
+            Err(problems) => {
+                for problem in problems {
+                    eprintln!("{} {}", e.paint("error:"), problem);
+                }
+            }
+        }
+
+        let input = readline(config, "Edit the plan again? [Y/n] ")?;
+        if input.trim().to_lowercase().starts_with('n') {
+            return Ok(Vec::new());
+        }
+    }
+}
+
+pub fn plan<'a>(
+    config: &Config,
+    backups: &[&'a Backup],
+    errors: &mut Vec<Error>,
+) -> Result<Vec<(&'a Backup, &'static str)>> {
+    let dir = private_temp_dir()?;
+    let res = edit_plan(config, backups, errors, &dir.join("plan"));
+    let _ = fs::remove_dir_all(&dir);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pacdiff::Kind;
+
+    fn backup(package: &str, file: &str, kind: Kind, missing: bool) -> Backup {
+        Backup {
+            root: "/".into(),
+            package: package.into(),
+            file: file.into(),
+            pacfiles: vec![format!("{}.{}", file, kind).into()],
+            kind,
+            missing,
+        }
+    }
+
+    fn backups() -> Vec<Backup> {
+        vec![
+            backup("openssh", "/etc/ssh/sshd_config", Kind::Pacnew, false),
+            backup("openssh", "/etc/ssh/sshd_config", Kind::Pacsave, false),
+            backup("pacman", "/etc/pacman.conf", Kind::Pacnew, true),
+        ]
+    }
+
+    fn parse(plan: &str) -> std::result::Result<Vec<(usize, &'static str)>, Vec<String>> {
+        let all = backups();
+        let backups = all.iter().collect::<Vec<_>>();
+        let steps = parse_plan(&backups, plan)?;
+
+        Ok(steps
+            .into_iter()
+            .map(|(b, action)| (all.iter().position(|a| std::ptr::eq(a, b)).unwrap(), action))
+            .collect())
+    }
+
+    #[test]
+    fn actions() {
+        assert_eq!(action("r", false), Some("remove"));
+        assert_eq!(action("overwrite", false), Some("overwrite"));
+        assert_eq!(action("s", true), Some("skip"));
+        assert_eq!(action("i", true), Some("install"));
+        assert_eq!(action("keep", true), Some("keep"));
+        assert_eq!(action("m", true), None);
+        assert_eq!(action("remove", true), None);
+        assert_eq!(action("install", false), None);
+        assert_eq!(action("x", false), None);
+    }
+
+    #[test]
+    fn written_plan_parses() {
+        let all = backups();
+        let backups = all.iter().collect::<Vec<_>>();
+        let plan = write_plan(&backups);
+
+        assert_eq!(
+            parse(&plan).unwrap(),
+            vec![(0, "skip"), (1, "skip"), (2, "skip")]
+        );
+    }
+
+    #[test]
+    fn valid_lines() {
+        let plan = "
+            o openssh pacnew /etc/ssh/sshd_config
+            r\topenssh  pacsave   /etc/ssh/sshd_config
+            # a comment
+            install pacman pacnew /etc/pacman.conf
+        ";
+
+        assert_eq!(
+            parse(plan).unwrap(),
+            vec![(0, "overwrite"), (1, "remove"), (2, "install")]
+        );
+        assert_eq!(parse("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn pacnew_and_pacsave() {
+        let plan = "
+            r openssh pacsave /etc/ssh/sshd_config
+            m openssh pacnew /etc/ssh/sshd_config
+        ";
+
+        assert_eq!(parse(plan).unwrap(), vec![(1, "remove"), (0, "merge")]);
+    }
+
+    #[test]
+    fn problems() {
+        let plan = "
+            o openssh pacnew /etc/ssh/sshd_config
+            r openssh pacnew /etc/ssh/sshd_config
+            o pacman pacnew /etc/pacman.conf
+            r pacman pacsave /etc/pacman.conf
+            s openssh /etc/ssh/sshd_config
+        ";
+
+        let problems = parse(plan).unwrap_err();
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("line 3:") && problems[0].contains("listed twice"));
+        assert!(problems[1].starts_with("line 4:") && problems[1].contains("invalid action"));
+        assert!(problems[2].starts_with("line 5:") && problems[2].contains("no pacfile"));
+        assert!(problems[3].starts_with("line 6:") && problems[3].contains("no pacfile"));
+    }
+}
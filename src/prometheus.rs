@@ -0,0 +1,99 @@
+use crate::error::{Error, Result};
+use crate::pacdiff::Backup;
+use crate::util::escape_path;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn metrics(backups: &[Backup]) -> String {
+    let now = SystemTime::now();
+    let mut counts = BTreeMap::new();
+    let mut ages = Vec::new();
+    let mut out = String::new();
+
+    for backup in backups {
+        let root = escape_path(&backup.root);
+        *counts
+            .entry((root, backup.kind.to_string(), backup.package.as_str()))
+            .or_insert(0) += backup.pacfiles.len();
+
+        for file in &backup.pacfiles {
+            let age = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|m| now.duration_since(m).ok());
+
+            if let Some(age) = age {
+                ages.push((backup, file, age.as_secs()));
+            }
+        }
+    }
+
+    out.push_str("# HELP pacdiffviewer_pacfiles Number of pending pacfiles.\n");
+    out.push_str("# TYPE pacdiffviewer_pacfiles gauge\n");
+    for ((root, kind, package), count) in &counts {
+        let _ = writeln!(
+            out,
+            "pacdiffviewer_pacfiles{{root=\"{}\",kind=\"{}\",package=\"{}\"}} {}",
+            escape(root),
+            kind,
+            escape(package),
+            count
+        );
+    }
+
+    let oldest = ages.iter().map(|(_, _, age)| *age).max().unwrap_or(0);
+    out.push_str(
+        "# HELP pacdiffviewer_oldest_pacfile_age_seconds Age of the oldest pending pacfile.\n",
+    );
+    out.push_str("# TYPE pacdiffviewer_oldest_pacfile_age_seconds gauge\n");
+    let _ = writeln!(out, "pacdiffviewer_oldest_pacfile_age_seconds {}", oldest);
+
+    out.push_str("# HELP pacdiffviewer_pacfile_age_seconds Age of each pending pacfile.\n");
+    out.push_str("# TYPE pacdiffviewer_pacfile_age_seconds gauge\n");
+    for (backup, file, age) in &ages {
+        let _ = writeln!(
+            out,
+            "pacdiffviewer_pacfile_age_seconds{{root=\"{}\",kind=\"{}\",package=\"{}\",path=\"{}\"}} {}",
+            escape(&escape_path(&backup.root)),
+            backup.kind,
+            escape(&backup.package),
+            escape(&escape_path(file)),
+            age
+        );
+    }
+
+    out
+}
+
+fn write_atomic(path: &Path, tmp: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)
+}
+
+pub fn write(path: &str, backups: &[Backup]) -> Result<()> {
+    let path = Path::new(path);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    write_atomic(path, &tmp, metrics(backups).as_bytes()).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        Error::File("write", path.into(), e)
+    })
+}
@@ -0,0 +1,310 @@
+use crate::pacdiff::{Backup, Kind};
+use crate::util::escape_path;
+
+use std::path::Path;
+use std::time::Duration;
+
+use glob::{MatchOptions, Pattern};
+
+const DAY: u64 = 24 * 60 * 60;
+
+enum Filter {
+    All,
+    Range(usize, usize),
+    Kind(Kind),
+    Older(Duration),
+    Newer(Duration),
+    Path(Pattern),
+    Package(Pattern),
+}
+
+struct Term {
+    text: String,
+    invert: bool,
+    filters: Vec<Filter>,
+}
+
+fn parse_age(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num = num.parse::<u64>().ok()?;
+
+    let secs = match unit {
+        "h" => 60 * 60,
+        "" | "d" => DAY,
+        "w" => 7 * DAY,
+        "m" => 30 * DAY,
+        "y" => 365 * DAY,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(num.checked_mul(secs)?))
+}
+
+fn parse_pattern(s: &str) -> Result<Pattern, String> {
+    Pattern::new(s).map_err(|e| format!("invalid pattern '{}': {}", s, e.msg))
+}
+
+fn is_package_pattern(s: &str) -> bool {
+    s.chars()
+        .all(|c| c.is_ascii_alphanumeric() || "@._+-*?[]!".contains(c))
+}
+
+fn parse_filter(s: &str, total: usize) -> Result<Filter, String> {
+    if s == "all" || s == "a" {
+        return Ok(Filter::All);
+    }
+    if s == "pacnew" {
+        return Ok(Filter::Kind(Kind::Pacnew));
+    }
+    if s == "pacsave" {
+        return Ok(Filter::Kind(Kind::Pacsave));
+    }
+    if let Some(age) = s.strip_prefix('>') {
+        let age = parse_age(age).ok_or_else(|| format!("invalid age '{}' (eg: >30d)", s))?;
+        return Ok(Filter::Older(age));
+    }
+    if let Some(age) = s.strip_prefix('<') {
+        let age = parse_age(age).ok_or_else(|| format!("invalid age '{}' (eg: <2w)", s))?;
+        return Ok(Filter::Newer(age));
+    }
+    if s.starts_with('/') {
+        return parse_pattern(s).map(Filter::Path);
+    }
+
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        let mut range = s.splitn(2, '-');
+        let min = range.next().unwrap_or("");
+        let max = range.next().unwrap_or(min);
+
+        if let (Ok(min), Ok(max)) = (min.parse::<usize>(), max.parse::<usize>()) {
+            let (min, max) = if min < max { (min, max) } else { (max, min) };
+            if min == 0 || max > total {
+                return Err(format!("'{}' is out of range (1-{})", s, total));
+            }
+            return Ok(Filter::Range(min, max));
+        }
+    }
+
+    if is_package_pattern(s) {
+        return parse_pattern(s).map(Filter::Package);
+    }
+
+    Err(format!("invalid selection '{}'", s))
+}
+
+fn parse(input: &str, total: usize) -> Result<Vec<Term>, String> {
+    let mut terms = Vec::new();
+
+    for token in input.split_whitespace() {
+        let (invert, rest) = match token.strip_prefix('^') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+
+        // "none" is kept from the old menu as a way to clear the selection
+        let (invert, rest) = match rest {
+            "none" | "n" => (!invert, "all"),
+            _ => (invert, rest),
+        };
+
+        let filters = rest
+            .split(',')
+            .map(|f| parse_filter(f, total))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        terms.push(Term {
+            text: token.to_string(),
+            invert,
+            filters,
+        });
+    }
+
+    Ok(terms)
+}
+
+// paths are matched as if the root were /, so /etc/* works for every root
+pub fn path_matches(pattern: &Pattern, backup: &Backup) -> bool {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+
+    let file = backup
+        .file
+        .strip_prefix(&backup.root)
+        .unwrap_or(&backup.file);
+    let file = escape_path(&Path::new("/").join(file));
+    pattern.matches_with(&file, options)
+}
+
+fn matches(filter: &Filter, n: usize, backup: &Backup) -> bool {
+    match filter {
+        Filter::All => true,
+        Filter::Range(min, max) => (*min..=*max).contains(&n),
+        Filter::Kind(kind) => backup.kind == *kind,
+        Filter::Older(age) => backup.age().filter(|a| a >= age).is_some(),
+        Filter::Newer(age) => backup.age().filter(|a| a < age).is_some(),
+        Filter::Path(pattern) => path_matches(pattern, backup),
+        Filter::Package(pattern) => pattern.matches(&backup.package),
+    }
+}
+
+// with strict set, a term that matches nothing is an error rather than a no-op
+pub fn select<'a>(
+    backups: &[&'a Backup],
+    input: &str,
+    strict: bool,
+) -> Result<Vec<&'a Backup>, String> {
+    if input.trim().is_empty() {
+        return Ok(backups.to_vec());
+    }
+
+    let terms = parse(input, backups.len())?;
+    let mut selected = vec![terms[0].invert; backups.len()];
+
+    for term in &terms {
+        let mut matched = false;
+
+        for (n, (backup, selected)) in backups.iter().zip(&mut selected).enumerate() {
+            if term.filters.iter().all(|f| matches(f, n + 1, backup)) {
+                *selected = !term.invert;
+                matched = true;
+            }
+        }
+
+        if strict && !matched {
+            return Err(format!("'{}' does not match any pacfile", term.text));
+        }
+    }
+
+    Ok(backups
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| *selected)
+        .map(|(backup, _)| *backup)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn backup(root: &str, package: &str, file: &str, kind: Kind) -> Backup {
+        let file = PathBuf::from(file);
+        Backup {
+            root: root.into(),
+            package: package.into(),
+            pacfiles: vec![file.with_extension(kind.to_string())],
+            file,
+            kind,
+            missing: false,
+        }
+    }
+
+    fn backups() -> Vec<Backup> {
+        vec![
+            backup("/", "openssh", "/etc/ssh/sshd_config", Kind::Pacnew),
+            backup("/", "openssh", "/etc/ssh/ssh_config", Kind::Pacsave),
+            backup("/", "pacman", "/etc/pacman.conf", Kind::Pacnew),
+            backup(
+                "/var/lib/machines/a",
+                "sudo",
+                "/var/lib/machines/a/etc/sudoers",
+                Kind::Pacnew,
+            ),
+        ]
+    }
+
+    fn numbers(input: &str) -> Result<Vec<usize>, String> {
+        let all = backups();
+        let backups = all.iter().collect::<Vec<_>>();
+        let selected = select(&backups, input, true)?;
+
+        Ok(selected
+            .iter()
+            .map(|s| all.iter().position(|b| std::ptr::eq(b, *s)).unwrap() + 1)
+            .collect())
+    }
+
+    #[test]
+    fn empty_selects_all() {
+        assert_eq!(numbers("").unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(numbers("all").unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(numbers("2").unwrap(), vec![2]);
+        assert_eq!(numbers("1-2 4").unwrap(), vec![1, 2, 4]);
+        assert_eq!(numbers("3-2").unwrap(), vec![2, 3]);
+        assert!(numbers("0").is_err());
+        assert!(numbers("2-5").is_err());
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(numbers("^2").unwrap(), vec![1, 3, 4]);
+        assert_eq!(numbers("1-3 ^2").unwrap(), vec![1, 3]);
+        assert_eq!(numbers("all ^openssh").unwrap(), vec![3, 4]);
+        assert_eq!(numbers("none").unwrap(), Vec::<usize>::new());
+        assert_eq!(numbers("none 4").unwrap(), vec![4]);
+    }
+
+    #[test]
+    fn globs() {
+        assert_eq!(numbers("/etc/ssh/*").unwrap(), vec![1, 2]);
+        assert_eq!(numbers("/etc/*.conf").unwrap(), vec![3]);
+        assert_eq!(numbers("/etc/sudoers").unwrap(), vec![4]);
+        assert_eq!(numbers("open*").unwrap(), vec![1, 2]);
+        assert_eq!(numbers("/etc/*").unwrap(), vec![3, 4]);
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(numbers("pacsave").unwrap(), vec![2]);
+        assert_eq!(numbers("pacnew,openssh").unwrap(), vec![1]);
+        assert_eq!(numbers("^pacnew").unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn ages() {
+        let dir = std::env::temp_dir().join(format!("pacdiffviewer-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("fresh.conf");
+        fs::write(file.with_extension("pacnew"), "").unwrap();
+
+        let fresh = backup("/", "fresh", file.to_str().unwrap(), Kind::Pacnew);
+        let missing = backup("/", "missing", "/nonexistent/file", Kind::Pacnew);
+        let backups = [&fresh, &missing];
+
+        let newer = select(&backups, "<1d", false).unwrap();
+        let older = select(&backups, ">1d", false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(newer.len(), 1);
+        assert!(std::ptr::eq(newer[0], &fresh));
+        assert!(older.is_empty());
+
+        assert_eq!(parse_age("2w"), Some(Duration::from_secs(14 * DAY)));
+        assert_eq!(parse_age("3"), Some(Duration::from_secs(3 * DAY)));
+        assert_eq!(parse_age("5x"), None);
+        assert_eq!(parse_age("1000000000000y"), None);
+        assert_eq!(parse_age("d"), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(numbers("<5x").is_err());
+        assert!(numbers("/etc/[").is_err());
+        assert!(numbers("foo/bar").is_err());
+        assert!(numbers("nosuchpackage").is_err());
+
+        let all = backups();
+        let backups = all.iter().collect::<Vec<_>>();
+        assert!(select(&backups, "nosuchpackage", false).unwrap().is_empty());
+    }
+}
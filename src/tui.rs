@@ -0,0 +1,459 @@
+use crate::binary;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::git::Repo;
+use crate::pacdiff::{readline, table, Backup, Outcome};
+use crate::util::escape_path;
+
+use std::io::{self, Stdout};
+use std::panic;
+use std::process::Command;
+
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use which::which;
+
+type Terminal = tui::Terminal<CrosstermBackend<Stdout>>;
+
+const HELP: &str = "j/k move  space mark  a mark all  v view  r remove  o overwrite  m merge  \
+                    x keep deleted  s skip  l gitlog  J/K scroll diff  q quit";
+
+struct Entry<'a> {
+    backup: &'a Backup,
+    outcome: Option<Outcome>,
+    marked: bool,
+}
+
+impl Entry<'_> {
+    // once the pacfile is gone there is nothing left to act on
+    fn done(&self) -> bool {
+        match self.outcome {
+            Some(Outcome::Skipped) | Some(Outcome::Failed) | None => false,
+            Some(_) => true,
+        }
+    }
+}
+
+struct State<'a> {
+    entries: Vec<Entry<'a>>,
+    list: ListState,
+    preview: Option<(usize, Vec<String>)>,
+    scroll: u16,
+}
+
+fn preview(backup: &Backup) -> Vec<String> {
+    let pacfile = &backup.pacfiles[backup.newest()];
+    let live = backup.live_file();
+
+    if binary::is_binary(live) || binary::is_binary(pacfile) {
+        return vec!["binary files, press v to compare them".into()];
+    }
+    if which("diff").is_err() {
+        return vec!["diff is not installed, press v to view the files".into()];
+    }
+
+    let output = match Command::new("diff")
+        .arg("-u")
+        .arg(live)
+        .arg(pacfile)
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => return vec![err.to_string()],
+    };
+
+    let lines = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.replace('\t', "    "))
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        vec!["files are identical".into()]
+    } else {
+        lines
+    }
+}
+
+fn color(color: ansi_term::Color) -> Color {
+    match color {
+        ansi_term::Color::Black => Color::Black,
+        ansi_term::Color::Red => Color::Red,
+        ansi_term::Color::Green => Color::Green,
+        ansi_term::Color::Yellow => Color::Yellow,
+        ansi_term::Color::Blue => Color::Blue,
+        ansi_term::Color::Purple => Color::Magenta,
+        ansi_term::Color::Cyan => Color::Cyan,
+        ansi_term::Color::White => Color::White,
+        ansi_term::Color::Fixed(n) => Color::Indexed(n),
+        ansi_term::Color::RGB(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+// the table styles are ansi_term ones, so they need converting for tui
+fn style(ansi: ansi_term::Style) -> Style {
+    let mut style = Style::default();
+
+    if let Some(fg) = ansi.foreground {
+        style = style.fg(color(fg));
+    }
+    if let Some(bg) = ansi.background {
+        style = style.bg(color(bg));
+    }
+
+    let modifiers = [
+        (ansi.is_bold, Modifier::BOLD),
+        (ansi.is_dimmed, Modifier::DIM),
+        (ansi.is_italic, Modifier::ITALIC),
+        (ansi.is_underline, Modifier::UNDERLINED),
+        (ansi.is_blink, Modifier::SLOW_BLINK),
+        (ansi.is_reverse, Modifier::REVERSED),
+    ];
+    for (set, modifier) in modifiers.iter() {
+        if *set {
+            style = style.add_modifier(*modifier);
+        }
+    }
+
+    style
+}
+
+fn rows(config: &Config, entries: &[Entry]) -> Vec<Spans<'static>> {
+    // stat errors only mean an unknown time here, the menu already reports them
+    let table = table(config, entries.iter().map(|e| e.backup), &mut Vec::new());
+    let widths = table.widths;
+    let last = widths.len() - 1;
+
+    entries
+        .iter()
+        .zip(table.rows)
+        .map(|(entry, row)| {
+            let mark = if entry.marked { "*" } else { " " };
+            let mut spans = vec![Span::raw(mark)];
+
+            for (n, ((cell, cell_style), width)) in row.into_iter().zip(&widths).enumerate() {
+                let pad = width - cell.chars().count();
+                if n > 0 {
+                    spans.push(Span::raw("  "));
+                }
+                if n == 0 {
+                    spans.push(Span::raw(format!("{}{}", "0".repeat(pad), cell)));
+                } else {
+                    spans.push(Span::styled(cell, style(cell_style)));
+                    if n != last {
+                        spans.push(Span::raw(" ".repeat(pad)));
+                    }
+                }
+            }
+
+            if let Some(outcome) = entry.outcome {
+                spans.push(Span::raw(format!("  {}", outcome)));
+            }
+
+            Spans::from(spans)
+        })
+        .collect()
+}
+
+fn diff_line<'a>(config: &Config, line: &'a str) -> Spans<'a> {
+    let style = if !config.color.enabled {
+        Style::default()
+    } else if line.starts_with("+++") || line.starts_with("---") {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else if line.starts_with('+') {
+        Style::default().fg(Color::Green)
+    } else if line.starts_with('-') {
+        Style::default().fg(Color::Red)
+    } else if line.starts_with("@@") {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+
+    Spans::from(Span::styled(line, style))
+}
+
+fn draw(config: &Config, terminal: &mut Terminal, state: &mut State) -> Result<()> {
+    let selected = state.list.selected().unwrap_or(0);
+
+    if state.preview.as_ref().map(|(n, _)| *n) != Some(selected) {
+        let entry = &state.entries[selected];
+        let lines = if entry.done() {
+            vec![format!(
+                "pacfile already handled: {}",
+                entry.outcome.unwrap()
+            )]
+        } else {
+            preview(entry.backup)
+        };
+        state.preview = Some((selected, lines));
+        state.scroll = 0;
+    }
+
+    let rows = rows(config, &state.entries);
+    let title = format!(" {} ", escape_path(&state.entries[selected].backup.file));
+
+    terminal.draw(|f| {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(40),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ])
+            .split(f.size());
+
+        let items = rows.iter().cloned().map(ListItem::new).collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Pacfiles "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, chunks[0], &mut state.list);
+
+        let lines = match state.preview {
+            Some((_, ref lines)) => lines.iter().map(|l| diff_line(config, l)).collect(),
+            None => Vec::new(),
+        };
+        let diff = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title.as_str()))
+            .scroll((state.scroll, 0));
+        f.render_widget(diff, chunks[1]);
+
+        f.render_widget(Paragraph::new(HELP), chunks[2]);
+    })?;
+
+    Ok(())
+}
+
+fn suspend(terminal: &mut Terminal) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+fn resume(terminal: &mut Terminal) -> Result<()> {
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.hide_cursor()?;
+    terminal.clear()?;
+    Ok(())
+}
+
+// a panic would otherwise leave the terminal in raw mode on the alternate screen
+fn restore_on_panic() {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+        hook(info);
+    }));
+}
+
+fn targets(state: &State) -> Vec<usize> {
+    let marked = state
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.marked && !e.done())
+        .map(|(n, _)| n)
+        .collect::<Vec<_>>();
+
+    if !marked.is_empty() {
+        return marked;
+    }
+
+    match state.list.selected() {
+        Some(n) if !state.entries[n].done() => vec![n],
+        _ => Vec::new(),
+    }
+}
+
+fn perform<'r, F>(
+    config: &Config,
+    terminal: &mut Terminal,
+    state: &mut State,
+    repo_for: &F,
+    errors: &mut Vec<Error>,
+    key: char,
+) -> Result<()>
+where
+    F: Fn(&Backup) -> Option<&'r Repo>,
+{
+    let targets = targets(state);
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    if key == 's' {
+        for n in targets {
+            state.entries[n].outcome = Some(Outcome::Skipped);
+            state.entries[n].marked = false;
+        }
+        return Ok(());
+    }
+
+    suspend(terminal)?;
+    let total = state.entries.len();
+    let mut failed = false;
+
+    for n in targets {
+        let entry = &mut state.entries[n];
+        let backup = entry.backup;
+        let preset = match (key, backup.missing) {
+            ('r', false) => "remove",
+            ('r', true) => "discard",
+            ('o', false) => "overwrite",
+            ('o', true) => "install",
+            ('m', false) => "merge",
+            ('x', true) => "keep",
+            _ => continue,
+        };
+
+        let outcome =
+            backup.manage(config, repo_for(backup), errors, n + 1, total, Some(preset))?;
+        failed |= outcome == Outcome::Failed;
+        entry.outcome = Some(outcome);
+        entry.marked = false;
+    }
+
+    if failed {
+        readline(config, "Press enter to continue ")?;
+    }
+
+    state.preview = None;
+    resume(terminal)
+}
+
+fn event_loop<'a, 'r, F>(
+    config: &Config,
+    terminal: &mut Terminal,
+    state: &mut State<'a>,
+    repo_for: &F,
+    errors: &mut Vec<Error>,
+) -> Result<()>
+where
+    F: Fn(&Backup) -> Option<&'r Repo>,
+{
+    let len = state.entries.len();
+
+    loop {
+        draw(config, terminal, state)?;
+
+        let (code, modifiers) = match event::read()? {
+            Event::Key(KeyEvent { code, modifiers }) => (code, modifiers),
+            _ => continue,
+        };
+
+        // raw mode swallows ^C, and a modified key must not act like the plain one
+        if modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(code, KeyCode::Char('c') | KeyCode::Char('d'))
+        {
+            return Ok(());
+        }
+        if !(modifiers - KeyModifiers::SHIFT).is_empty() {
+            continue;
+        }
+
+        let selected = state.list.selected().unwrap_or(0);
+        let page = terminal.size()?.height as usize / 3;
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.list.select(Some((selected + 1).min(len - 1)))
+            }
+            KeyCode::Up | KeyCode::Char('k') => state.list.select(Some(selected.saturating_sub(1))),
+            KeyCode::PageDown => state.list.select(Some((selected + page).min(len - 1))),
+            KeyCode::PageUp => state.list.select(Some(selected.saturating_sub(page))),
+            KeyCode::Home | KeyCode::Char('g') => state.list.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => state.list.select(Some(len - 1)),
+            KeyCode::Char('J') => state.scroll = state.scroll.saturating_add(1),
+            KeyCode::Char('K') => state.scroll = state.scroll.saturating_sub(1),
+            KeyCode::Char(' ') => {
+                let entry = &mut state.entries[selected];
+                entry.marked = !entry.marked && !entry.done();
+                state.list.select(Some((selected + 1).min(len - 1)));
+            }
+            KeyCode::Char('a') => {
+                let mark = state.entries.iter().any(|e| !e.marked && !e.done());
+                for entry in &mut state.entries {
+                    entry.marked = mark && !entry.done();
+                }
+            }
+            KeyCode::Char('v') if !state.entries[selected].done() => {
+                let backup = state.entries[selected].backup;
+                suspend(terminal)?;
+                let res = backup.view(config, &backup.pacfiles[backup.newest()]);
+                resume(terminal)?;
+                if let Err(err) = res {
+                    errors.push(err);
+                }
+            }
+            KeyCode::Char('l') => {
+                let backup = state.entries[selected].backup;
+                if let Some(repo) = repo_for(backup) {
+                    suspend(terminal)?;
+                    let res = repo.log(&backup.file);
+                    resume(terminal)?;
+                    if let Err(err) = res {
+                        errors.push(err);
+                    }
+                }
+            }
+            KeyCode::Char(c) if "sromx".contains(c) => {
+                perform(config, terminal, state, repo_for, errors, c)?
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn run<'a, 'r, F>(
+    config: &Config,
+    backups: &[&'a Backup],
+    repo_for: F,
+    errors: &mut Vec<Error>,
+) -> Result<Vec<(&'a Backup, Outcome)>>
+where
+    F: Fn(&Backup) -> Option<&'r Repo>,
+{
+    if backups.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut state = State {
+        entries: backups
+            .iter()
+            .map(|backup| Entry {
+                backup,
+                outcome: None,
+                marked: false,
+            })
+            .collect(),
+        list: ListState::default(),
+        preview: None,
+        scroll: 0,
+    };
+    state.list.select(Some(0));
+
+    restore_on_panic();
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    resume(&mut terminal)?;
+    let res = event_loop(config, &mut terminal, &mut state, &repo_for, errors);
+    suspend(&mut terminal)?;
+    res?;
+
+    Ok(state
+        .entries
+        .into_iter()
+        .filter_map(|e| e.outcome.map(|o| (e.backup, o)))
+        .collect())
+}
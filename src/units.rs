@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::pacdiff::readline;
+
+use std::process::Command;
+
+use alpm::Alpm;
+use which::which;
+
+const UNIT_DIRS: &[&str] = &["usr/lib/systemd/system/", "etc/systemd/system/"];
+
+fn units(alpm: &Alpm, package: &str) -> Vec<String> {
+    let pkg = match alpm.localdb().pkg(package) {
+        Ok(pkg) => pkg,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut units = Vec::new();
+
+    for file in pkg.files().files() {
+        let name = file.name();
+        let unit = match UNIT_DIRS.iter().find_map(|dir| name.strip_prefix(dir)) {
+            Some(unit) => unit,
+            None => continue,
+        };
+
+        if unit.contains('/') || !unit.ends_with(".service") || unit.ends_with("@.service") {
+            continue;
+        }
+
+        if !units.iter().any(|u| u == unit) {
+            units.push(unit.to_string());
+        }
+    }
+
+    units
+}
+
+fn is_active(unit: &str) -> bool {
+    Command::new("systemctl")
+        .args(["is-active", "--quiet", unit])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+pub fn suggest_restarts(config: &Config, alpm: &Alpm, packages: &[String]) -> Result<()> {
+    if alpm.root() != "/" || packages.is_empty() || which("systemctl").is_err() {
+        return Ok(());
+    }
+
+    let mut restart = Vec::new();
+
+    for package in packages {
+        let active = units(alpm, package)
+            .into_iter()
+            .filter(|u| is_active(u))
+            .collect::<Vec<_>>();
+
+        if !active.is_empty() {
+            restart.push((package, active));
+        }
+    }
+
+    if restart.is_empty() {
+        return Ok(());
+    }
+
+    let b = config.color.bold;
+    println!(
+        "\n{} {}",
+        config.color.prompt.paint("::"),
+        b.paint("Services using changed config files may need to be restarted:")
+    );
+    for (package, units) in &restart {
+        println!("    {}: {}", package, units.join(" "));
+    }
+
+    let confirmed = if config.reload_units {
+        true
+    } else if config.action.is_none() {
+        let input = readline(config, "Reload or restart them? [y/N] ")?;
+        input.trim().to_lowercase().starts_with('y')
+    } else {
+        false
+    };
+
+    if !confirmed {
+        return Ok(());
+    }
+
+    let mut args = vec!["reload-or-restart".to_string()];
+    args.extend(restart.into_iter().flat_map(|(_, units)| units));
+
+    let exit = Command::new("systemctl")
+        .args(&args)
+        .status()
+        .map_err(|err| Error::CommandFailed("systemctl".into(), args.clone(), err))?;
+
+    if !exit.success() {
+        return Err(Error::CommandNonZero("systemctl".into(), args, exit.code()));
+    }
+
+    Ok(())
+}
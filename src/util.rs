@@ -0,0 +1,35 @@
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+fn push_escaped(out: &mut String, s: &str) {
+    for c in s.chars() {
+        if c.is_control() {
+            out.extend(c.escape_default());
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+pub fn escape_path(path: &Path) -> String {
+    let mut bytes = path.as_os_str().as_bytes();
+    let mut out = String::new();
+
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                push_escaped(&mut out, s);
+                return out;
+            }
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                let invalid = err.error_len().unwrap_or(rest.len());
+                push_escaped(&mut out, std::str::from_utf8(valid).unwrap_or_default());
+                for byte in &rest[..invalid] {
+                    out.push_str(&format!("\\x{:02x}", byte));
+                }
+                bytes = &rest[invalid..];
+            }
+        }
+    }
+}
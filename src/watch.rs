@@ -0,0 +1,130 @@
+use crate::config::Config;
+use crate::deleted;
+use crate::error::{Error, Result};
+use crate::hooks::shell;
+use crate::pacdiff::{backups_for_file, pacfile_kind, package_files, wanted_backup, Backup};
+use crate::util::escape_path;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use alpm::Alpm;
+use inotify::{EventMask, Inotify, WatchMask};
+
+fn notify(config: &Config, backup: &Backup, pacfile: &Path) -> Result<()> {
+    let cmd = match config.notify {
+        Some(ref cmd) => cmd,
+        None => {
+            println!(
+                "{} new {}: {} ({})",
+                config.color.info.paint("==>"),
+                backup.kind,
+                escape_path(pacfile),
+                backup.package
+            );
+            return Ok(());
+        }
+    };
+
+    shell(cmd, backup, &[("PACDIFF_PACFILE", pacfile.as_os_str())])
+}
+
+fn print_errors(config: &Config, errors: &mut Vec<Error>) {
+    for err in errors.drain(..) {
+        eprintln!("{} {}", config.color.error.paint("error:"), err);
+    }
+}
+
+pub fn watch(config: &Config, alpms: &[Alpm]) -> Result<()> {
+    let e = config.color.error;
+    let mut dirs = BTreeMap::<PathBuf, Vec<(&Path, String, PathBuf)>>::new();
+    let mut seen = HashSet::new();
+    let mut watches = HashMap::new();
+    let mut errors = Vec::new();
+    let mut inotify = Inotify::init()?;
+
+    for alpm in alpms {
+        let root = Path::new(alpm.root());
+
+        // target errors are printed as they are found
+        for (package, file) in package_files(config, alpm, &mut Vec::new()) {
+            for backup in backups_for_file(config, &mut errors, root, &package, &file) {
+                seen.extend(backup.pacfiles);
+            }
+
+            if let Some(parent) = file.parent() {
+                let parent = parent.to_path_buf();
+                dirs.entry(parent).or_default().push((root, package, file));
+            }
+        }
+    }
+
+    print_errors(config, &mut errors);
+
+    let mask =
+        WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE | WatchMask::MOVED_FROM;
+
+    for dir in dirs.keys() {
+        match inotify.add_watch(dir, mask) {
+            Ok(wd) => {
+                watches.insert(wd, dir);
+            }
+            Err(err) => {
+                if config.verbose {
+                    let err = Error::File("watch", dir.clone(), err);
+                    eprintln!("{} {}", e.paint("error:"), err);
+                }
+            }
+        }
+    }
+
+    let mut buffer = [0; 4096];
+
+    loop {
+        for event in inotify.read_events_blocking(&mut buffer)? {
+            let (dir, name) = match (watches.get(&event.wd), event.name) {
+                (Some(dir), Some(name)) => (*dir, name),
+                _ => continue,
+            };
+
+            let path = dir.join(name);
+
+            if event
+                .mask
+                .intersects(EventMask::DELETE | EventMask::MOVED_FROM)
+            {
+                seen.remove(&path);
+                continue;
+            }
+
+            if seen.contains(&path) {
+                continue;
+            }
+
+            for (root, package, file) in &dirs[dir] {
+                if pacfile_kind(file, name).is_none() {
+                    continue;
+                }
+
+                seen.insert(path.clone());
+
+                let backups = backups_for_file(config, &mut errors, root, package, file);
+                print_errors(config, &mut errors);
+
+                // read every time, a file may have been marked deleted since the start
+                let deleted = deleted::load(root);
+
+                for backup in backups {
+                    if !backup.pacfiles.contains(&path) || !wanted_backup(config, &deleted, &backup)
+                    {
+                        continue;
+                    }
+
+                    if let Err(err) = notify(config, &backup, &path) {
+                        eprintln!("{} {}", e.paint("error:"), err);
+                    }
+                }
+            }
+        }
+    }
+}